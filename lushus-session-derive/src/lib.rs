@@ -0,0 +1,51 @@
+//! Derive macro for `lushus_session::SessionSection`. Use it through the
+//! `derive` feature of `lushus-session` rather than depending on this crate.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, LitStr};
+
+/// Implements `SessionSection`, storing the struct's fields under
+/// `<prefix>.<field>`. The prefix defaults to the struct name in snake case
+/// and can be set with `#[session(prefix = "...")]`.
+#[proc_macro_derive(SessionSection, attributes(session))]
+pub fn derive_session_section(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut prefix = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("session")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                prefix = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported session attribute"))
+            }
+        })?;
+    }
+    let name = &input.ident;
+    let prefix = prefix.unwrap_or_else(|| snake_case(&name.to_string()));
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::lushus_session::SessionSection for #name #ty_generics #where_clause {
+            const PREFIX: &'static str = #prefix;
+        }
+    })
+}
+
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len());
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
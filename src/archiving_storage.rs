@@ -0,0 +1,320 @@
+use std::{borrow::Cow, io::Write, sync::Mutex, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+use serde::Serialize;
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchivingStorageError<StorageError, ArchiveError> {
+    #[error("Archive error: {0}")]
+    ArchiveError(ArchiveError),
+    #[error(transparent)]
+    StorageError(StorageError),
+}
+
+/// Why a session is being archived.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveReason {
+    /// The session was destroyed or taken, see
+    /// [`crate::SessionStorageWrite::session_take`]. Storage sees the same
+    /// removal for both, so they cannot be told apart here.
+    Destroyed,
+    /// The session moved to a new key and lives on under it.
+    Rotated,
+    /// The session was replaced by the tombstone left by
+    /// [`crate::SessionModel::destroy_soft`].
+    LoggedOut,
+}
+
+/// Cold storage for sessions that are about to be deleted, e.g. a file,
+/// object store or database table kept for analytics or retention.
+pub trait ArchiveSink {
+    type Error;
+
+    fn archive(
+        &self,
+        key: &SessionKey,
+        state: &SessionState,
+        reason: ArchiveReason,
+    ) -> Result<(), Self::Error>;
+}
+
+#[derive(Serialize)]
+struct ArchiveLine<'a> {
+    reason: ArchiveReason,
+    state: &'a SessionState,
+}
+
+/// Writes each archived session state as a line of JSON along with the reason
+/// it was archived. Session keys are not written.
+pub struct JsonLinesArchiveSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write> JsonLinesArchiveSink<W> {
+    pub fn new(writer: W) -> Self {
+        let writer = Mutex::new(writer);
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl<W: Write> ArchiveSink for JsonLinesArchiveSink<W> {
+    type Error = std::io::Error;
+
+    fn archive(
+        &self,
+        _key: &SessionKey,
+        state: &SessionState,
+        reason: ArchiveReason,
+    ) -> Result<(), Self::Error> {
+        let mut line = serde_json::to_vec(&ArchiveLine { reason, state })?;
+        line.push(b'\n');
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        writer.write_all(&line)?;
+        writer.flush()
+    }
+}
+
+/// Wraps session storage and copies every session to `archive` before it is
+/// destroyed or overwritten by a tombstone. If archiving fails the session is
+/// kept and the destroy fails.
+///
+/// Sessions that the backend expires on its own are never seen here; only
+/// explicit destroys, including those of expired sessions, are archived.
+pub struct ArchivingStorage<S, A> {
+    storage: S,
+    archive: A,
+    rotated: Option<SessionKey>,
+}
+
+impl<S, A> ArchivingStorage<S, A> {
+    pub fn new(storage: S, archive: A) -> Self {
+        Self {
+            storage,
+            archive,
+            rotated: None,
+        }
+    }
+
+    pub fn into_parts(self) -> (S, A) {
+        (self.storage, self.archive)
+    }
+}
+
+impl<S: Storage, A: ArchiveSink> Storage for ArchivingStorage<S, A> {
+    type Error = ArchivingStorageError<S::Error, A::Error>;
+}
+
+impl<S, A> StorageRead<SessionStateTable> for ArchivingStorage<S, A>
+where
+    S: StorageRead<SessionStateTable>,
+    A: ArchiveSink,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.storage
+            .get(key)
+            .map_err(ArchivingStorageError::StorageError)
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage
+            .exists(key)
+            .map_err(ArchivingStorageError::StorageError)
+    }
+}
+
+impl<S, A> StorageWrite<SessionStateTable> for ArchivingStorage<S, A>
+where
+    S: StorageRead<SessionStateTable> + StorageWrite<SessionStateTable>,
+    A: ArchiveSink,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        if value.metadata().is_tombstone() {
+            self.archive_stored(key, ArchiveReason::LoggedOut)?;
+        }
+        let previous = self
+            .storage
+            .insert(key, value)
+            .map_err(ArchivingStorageError::StorageError)?;
+        // The previous key is destroyed right after the session is stored
+        // under its new one.
+        self.rotated = value.metadata().replaces().cloned();
+        Ok(previous)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        let reason = match self.rotated.take() {
+            Some(rotated) if &rotated == key => ArchiveReason::Rotated,
+            _ => ArchiveReason::Destroyed,
+        };
+        self.archive_stored(key, reason)?;
+        self.storage
+            .remove(key)
+            .map_err(ArchivingStorageError::StorageError)
+    }
+}
+
+impl<S, A> ArchivingStorage<S, A>
+where
+    S: StorageRead<SessionStateTable>,
+    A: ArchiveSink,
+{
+    fn archive_stored(
+        &self,
+        key: &SessionKey,
+        reason: ArchiveReason,
+    ) -> Result<(), ArchivingStorageError<S::Error, A::Error>> {
+        let state = self
+            .storage
+            .get(key)
+            .map_err(ArchivingStorageError::StorageError)?;
+        match state {
+            Some(state) if !state.metadata().is_tombstone() => self
+                .archive
+                .archive(key, &state, reason)
+                .map_err(ArchivingStorageError::ArchiveError),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<S, A> StorageTemp<SessionStateTable> for ArchivingStorage<S, A>
+where
+    S: StorageTemp<SessionStateTable>,
+    A: ArchiveSink,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage
+            .ttl(key)
+            .map_err(ArchivingStorageError::StorageError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageError, SessionStorageWrite};
+
+    struct FailingSink;
+
+    impl ArchiveSink for FailingSink {
+        type Error = String;
+
+        fn archive(
+            &self,
+            _key: &SessionKey,
+            _state: &SessionState,
+            _reason: ArchiveReason,
+        ) -> Result<(), Self::Error> {
+            Err("archive unavailable".to_string())
+        }
+    }
+
+    fn saved_session<S>(storage: &mut S) -> SessionKey
+    where
+        S: SessionStorageWrite,
+        S::Error: std::fmt::Debug,
+    {
+        let mut session = Session::default();
+        session
+            .insert("name", &"fred".to_string())
+            .expect("expected insert to succeed");
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        session.id().clone()
+    }
+
+    #[test]
+    fn archiving_storage_archives_sessions_before_destroying_them() {
+        let sink = JsonLinesArchiveSink::new(Vec::new());
+        let mut storage = ArchivingStorage::new(MockStorage::new(), sink);
+        let key = saved_session(&mut storage);
+        storage
+            .session_destroy(&key)
+            .expect("expected destroy to succeed");
+
+        let (storage, sink) = storage.into_parts();
+        assert!(storage.map.is_empty());
+        let output = String::from_utf8(sink.into_inner()).expect("expected output to be UTF-8");
+        let line = serde_json::from_str::<serde_json::Value>(output.trim_end())
+            .expect("expected an archived line");
+        assert_eq!(line["reason"], "destroyed");
+        let state = serde_json::from_value::<SessionState>(line["state"].clone())
+            .expect("expected an archived session state");
+        assert_eq!(
+            state.get("name").and_then(|value| value.as_json()),
+            Some(&serde_json::json!("fred"))
+        );
+        assert!(!output.contains(key.as_ref()));
+    }
+
+    #[test]
+    fn archiving_storage_records_why_sessions_are_archived() {
+        let sink = JsonLinesArchiveSink::new(Vec::new());
+        let mut storage = ArchivingStorage::new(MockStorage::new(), sink);
+        let key = saved_session(&mut storage);
+        let mut session = Session::new(key.clone(), storage.storage.map[&key].clone());
+        session
+            .regenerate()
+            .expect("expected regenerate to succeed");
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        storage
+            .session_destroy(&key)
+            .expect("expected destroy to succeed");
+        let mut tombstone = Session::new(session.id().clone(), Default::default());
+        tombstone.tombstone(Duration::from_secs(60));
+        storage
+            .session_save(&tombstone)
+            .expect("expected save to succeed");
+        storage
+            .session_destroy(session.id())
+            .expect("expected destroy to succeed");
+
+        let (_, sink) = storage.into_parts();
+        let output = String::from_utf8(sink.into_inner()).expect("expected output to be UTF-8");
+        let reasons = output
+            .lines()
+            .map(|line| {
+                let line = serde_json::from_str::<serde_json::Value>(line)
+                    .expect("expected an archived line");
+                line["reason"].as_str().map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            reasons,
+            vec![Some("rotated".to_string()), Some("logged_out".to_string())]
+        );
+    }
+
+    #[test]
+    fn archiving_storage_keeps_sessions_it_cannot_archive() {
+        let mut storage = ArchivingStorage::new(MockStorage::new(), FailingSink);
+        let key = saved_session(&mut storage);
+        let result = storage.session_destroy(&key);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                ArchivingStorageError::ArchiveError(_)
+            ))
+        ));
+        assert!(storage.storage.map.contains_key(&key));
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    borrow::Cow,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+use serde::Serialize;
+
+use crate::{
+    session_key::sha256_hex, session_storage::SessionStateTable, Session, SessionEventHandler,
+    SessionKey, SessionState,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// A single audited storage operation. The session key is never recorded,
+/// only a SHA-256 hash of it that can be correlated across records.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct AuditRecord {
+    pub operation: &'static str,
+    pub key_hash: String,
+    pub user_id: Option<String>,
+    /// `None` for records from [`AuditEvents`], which only sees operations
+    /// that succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<AuditOutcome>,
+    pub timestamp: SystemTime,
+}
+
+pub trait AuditSink {
+    fn record(&self, record: &AuditRecord);
+}
+
+impl<A: AuditSink + ?Sized> AuditSink for Arc<A> {
+    fn record(&self, record: &AuditRecord) {
+        (**self).record(record);
+    }
+}
+
+/// Writes each record as a line of JSON. Records that cannot be written are
+/// dropped rather than failing the session operation.
+pub struct JsonLinesAuditSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write> JsonLinesAuditSink<W> {
+    pub fn new(writer: W) -> Self {
+        let writer = Mutex::new(writer);
+        Self { writer }
+    }
+}
+
+impl JsonLinesAuditSink<File> {
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl<W: Write> AuditSink for JsonLinesAuditSink<W> {
+    fn record(&self, record: &AuditRecord) {
+        let Ok(mut line) = serde_json::to_vec(record) else {
+            return;
+        };
+        line.push(b'\n');
+        let Ok(mut writer) = self.writer.lock() else {
+            return;
+        };
+        if let Err(_e) = writer.write_all(&line).and_then(|()| writer.flush()) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "failed to write audit record");
+        }
+    }
+}
+
+/// Reports the lifecycle events of [`crate::SessionModel`] to `sink` as
+/// `create`, `save`, `destroy` and `expire` records, once registered with
+/// [`crate::SessionEvents::register`]. Unlike [`Audited`], only what the application
+/// did to its sessions is recorded.
+pub struct AuditEvents<A> {
+    sink: A,
+}
+
+impl<A> AuditEvents<A> {
+    pub fn new(sink: A) -> Self {
+        Self { sink }
+    }
+}
+
+impl<A: AuditSink> AuditEvents<A> {
+    fn audit(&self, operation: &'static str, session: &Session) {
+        let record = AuditRecord {
+            operation,
+            key_hash: hash(session.id()),
+            user_id: session.user_id().map(str::to_string),
+            outcome: None,
+            timestamp: SystemTime::now(),
+        };
+        self.sink.record(&record);
+    }
+}
+
+impl<A: AuditSink + Send + Sync> SessionEventHandler for AuditEvents<A> {
+    fn on_create(&self, session: &Session) {
+        self.audit("create", session);
+    }
+
+    fn on_save(&self, session: &Session) {
+        self.audit("save", session);
+    }
+
+    fn on_destroy(&self, session: &Session) {
+        self.audit("destroy", session);
+    }
+
+    fn on_expire(&self, session: &Session) {
+        self.audit("expire", session);
+    }
+}
+
+/// Wraps session storage and reports every load, save and destroy to `sink`.
+///
+/// These are storage operations, not what the application did: the read
+/// behind optimistic locking is recorded as a load, taking a session as a
+/// destroy, and moving a session to a new key as a save and a destroy. Use
+/// [`AuditEvents`] to record session lifecycle events instead.
+pub struct Audited<S, A> {
+    storage: S,
+    sink: A,
+}
+
+impl<S, A> Audited<S, A> {
+    pub fn new(storage: S, sink: A) -> Self {
+        Self { storage, sink }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S, A: AuditSink> Audited<S, A> {
+    fn audit<T, E>(
+        &self,
+        operation: &'static str,
+        key: &SessionKey,
+        result: &Result<T, E>,
+        user_id: impl FnOnce(&T) -> Option<String>,
+    ) {
+        let (outcome, user_id) = match result {
+            Ok(value) => (AuditOutcome::Success, user_id(value)),
+            Err(_) => (AuditOutcome::Failure, None),
+        };
+        let record = AuditRecord {
+            operation,
+            key_hash: hash(key),
+            user_id,
+            outcome: Some(outcome),
+            timestamp: SystemTime::now(),
+        };
+        self.sink.record(&record);
+    }
+}
+
+fn hash(key: &SessionKey) -> String {
+    sha256_hex(key.as_ref().as_bytes())
+}
+
+fn user_id(state: Option<&SessionState>) -> Option<String> {
+    state.and_then(|state| state.metadata().user_id().map(str::to_string))
+}
+
+impl<S: Storage, A> Storage for Audited<S, A> {
+    type Error = S::Error;
+}
+
+impl<S, A> StorageRead<SessionStateTable> for Audited<S, A>
+where
+    S: StorageRead<SessionStateTable>,
+    A: AuditSink,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        let result = self.storage.get(key);
+        self.audit("load", key, &result, |state| user_id(state.as_deref()));
+        result
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage.exists(key)
+    }
+}
+
+impl<S, A> StorageWrite<SessionStateTable> for Audited<S, A>
+where
+    S: StorageWrite<SessionStateTable>,
+    A: AuditSink,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let result = self.storage.insert(key, value);
+        self.audit("save", key, &result, |_| user_id(Some(value)));
+        result
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        let result = self.storage.remove(key);
+        self.audit("destroy", key, &result, |state| user_id(state.as_ref()));
+        result
+    }
+}
+
+impl<S, A> StorageTemp<SessionStateTable> for Audited<S, A>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage.ttl(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, SessionEvents, SessionModel, SessionStorageWrite};
+
+    #[test]
+    fn audited_writes_a_json_line_per_operation() {
+        let sink = JsonLinesAuditSink::new(Vec::new());
+        let mut storage = Audited::new(MockStorage::new(), sink);
+        let mut session = Session::default();
+        session
+            .set_user_id(Some("fred".to_string()))
+            .expect("expected set_user_id to succeed");
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        storage
+            .session_destroy(session.id())
+            .expect("expected destroy to succeed");
+
+        let output = storage.sink.writer.into_inner().unwrap();
+        let lines = String::from_utf8(output).expect("expected output to be UTF-8");
+        let records = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["operation"], "save");
+        assert_eq!(records[0]["user_id"], "fred");
+        assert_eq!(records[0]["outcome"], "success");
+        assert_eq!(records[1]["operation"], "destroy");
+        assert!(!lines.contains(session.id().as_ref()));
+    }
+
+    #[test]
+    fn audit_events_record_model_operations() {
+        let sink = Arc::new(JsonLinesAuditSink::new(Vec::new()));
+        let mut events = SessionEvents::new();
+        events.register(AuditEvents::new(sink.clone()));
+        let mut model = SessionModel::new(MockStorage::new(), Duration::from_secs(100));
+        model.set_events(events);
+        model
+            .authenticate("fred".to_string())
+            .expect("expected authenticate to succeed");
+        model.destroy().expect("expected destroy to succeed");
+        drop(model);
+
+        let sink = Arc::into_inner(sink).expect("expected the sink to be released");
+        let output = sink.writer.into_inner().unwrap();
+        let lines = String::from_utf8(output).expect("expected output to be UTF-8");
+        let records = lines
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        let operations = records
+            .iter()
+            .map(|record| record["operation"].as_str().map(str::to_string))
+            .collect::<Vec<_>>();
+        assert!(records.iter().all(|record| record.get("outcome").is_none()));
+        assert_eq!(
+            operations,
+            ["create", "save", "destroy"].map(|operation| Some(operation.to_string()))
+        );
+    }
+}
@@ -0,0 +1,285 @@
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+#[derive(Debug, thiserror::Error)]
+pub enum CircuitBreakerError<StorageError> {
+    #[error("Session storage is unavailable")]
+    OpenError,
+    #[error(transparent)]
+    StorageError(StorageError),
+}
+
+/// Wraps session storage and stops calling it after `threshold` consecutive
+/// failures. While open, every call fails with
+/// [`CircuitBreakerError::OpenError`] until `cooldown` has passed, after which
+/// a single call is let through as a probe: success closes the breaker,
+/// failure opens it for another `cooldown`.
+pub struct CircuitBreaker<S> {
+    storage: S,
+    breaker: Breaker,
+}
+
+struct Breaker {
+    threshold: u32,
+    cooldown: Duration,
+    fail_open: bool,
+    failures: AtomicU32,
+    opened_at: Mutex<Option<Instant>>,
+    probing: AtomicBool,
+}
+
+impl<S> CircuitBreaker<S> {
+    pub fn new(storage: S, threshold: u32, cooldown: Duration) -> Self {
+        let breaker = Breaker {
+            threshold,
+            cooldown,
+            fail_open: false,
+            failures: AtomicU32::new(0),
+            opened_at: Mutex::new(None),
+            probing: AtomicBool::new(false),
+        };
+        Self { storage, breaker }
+    }
+
+    /// While open, serve every session as missing instead of failing, so
+    /// requests carry on with a fresh session. Writes still fail with
+    /// [`CircuitBreakerError::OpenError`], so a save or a logout is never
+    /// reported as done when it did not reach storage.
+    pub fn set_fail_open(&mut self, enabled: bool) {
+        self.breaker.fail_open = enabled;
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.breaker.is_open()
+    }
+}
+
+impl Breaker {
+    fn opened_at(&self) -> Option<Instant> {
+        *self
+            .opened_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_opened_at(&self, opened_at: Option<Instant>) {
+        *self
+            .opened_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = opened_at;
+    }
+
+    fn is_open(&self) -> bool {
+        self.opened_at().is_some_and(|opened_at| {
+            opened_at.elapsed() < self.cooldown || self.probing.load(Ordering::Acquire)
+        })
+    }
+
+    /// Whether a call may go through, and if so whether it is the probe.
+    fn admit(&self) -> Option<bool> {
+        match self.opened_at() {
+            None => Some(false),
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => None,
+            Some(_) => {
+                let probing = self.probing.swap(true, Ordering::AcqRel);
+                (!probing).then_some(true)
+            }
+        }
+    }
+
+    fn call<T, E>(&self, f: impl FnOnce() -> Result<T, E>) -> Result<T, CircuitBreakerError<E>> {
+        let Some(probe) = self.admit() else {
+            return Err(CircuitBreakerError::OpenError);
+        };
+        let result = f();
+        match &result {
+            Ok(_) => {
+                self.failures.store(0, Ordering::Release);
+                self.set_opened_at(None);
+            }
+            Err(_) => {
+                let failures = self.failures.fetch_add(1, Ordering::AcqRel) + 1;
+                if probe || failures >= self.threshold {
+                    self.set_opened_at(Some(Instant::now()));
+                }
+            }
+        }
+        if probe {
+            self.probing.store(false, Ordering::Release);
+        }
+        result.map_err(CircuitBreakerError::StorageError)
+    }
+
+    /// Like [`Breaker::call`], but returns `fallback` instead of
+    /// [`CircuitBreakerError::OpenError`] when failing open.
+    fn call_or<T, E>(
+        &self,
+        fallback: T,
+        f: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, CircuitBreakerError<E>> {
+        match self.call(f) {
+            Err(CircuitBreakerError::OpenError) if self.fail_open => Ok(fallback),
+            result => result,
+        }
+    }
+}
+
+impl<S: Storage> Storage for CircuitBreaker<S> {
+    type Error = CircuitBreakerError<S::Error>;
+}
+
+impl<S> StorageRead<SessionStateTable> for CircuitBreaker<S>
+where
+    S: StorageRead<SessionStateTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.breaker.call_or(None, || self.storage.get(key))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.breaker.call_or(false, || self.storage.exists(key))
+    }
+}
+
+impl<S> StorageWrite<SessionStateTable> for CircuitBreaker<S>
+where
+    S: StorageWrite<SessionStateTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        self.breaker.call(|| self.storage.insert(key, value))
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.breaker.call(|| self.storage.remove(key))
+    }
+}
+
+impl<S> StorageTemp<SessionStateTable> for CircuitBreaker<S>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.breaker
+            .call_or(Duration::ZERO, || self.storage.ttl(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{MockOperation, MockStorage, MockStorageError},
+        Session, SessionStorageError, SessionStorageRead, SessionStorageWrite,
+    };
+
+    fn failing_storage(failures: usize) -> MockStorage {
+        let storage = MockStorage::new();
+        for _ in 0..failures {
+            let error = MockStorageError("connection refused".to_string());
+            storage.fail_next(MockOperation::Insert, error);
+        }
+        storage
+    }
+
+    #[test]
+    fn circuit_breaker_fails_fast_after_the_threshold() {
+        let mut storage = CircuitBreaker::new(failing_storage(2), 2, Duration::from_secs(60));
+        let session = Session::default();
+        for _ in 0..2 {
+            let result = storage.session_save(&session);
+            assert!(matches!(
+                result,
+                Err(SessionStorageError::StorageError(
+                    CircuitBreakerError::StorageError(_)
+                ))
+            ));
+        }
+        assert!(storage.is_open());
+        let result = storage.session_save(&session);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                CircuitBreakerError::OpenError
+            ))
+        ));
+    }
+
+    #[test]
+    fn circuit_breaker_lets_a_probe_through_after_the_cooldown() {
+        let mut storage = CircuitBreaker::new(failing_storage(2), 1, Duration::ZERO);
+        let session = Session::default();
+        let _ = storage.session_save(&session);
+        assert!(!storage.is_open());
+        let result = storage.session_save(&session);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                CircuitBreakerError::StorageError(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn circuit_breaker_lets_a_single_probe_through() {
+        let storage = CircuitBreaker::new(MockStorage::new(), 1, Duration::ZERO);
+        let breaker = &storage.breaker;
+        let _ = breaker.call(|| Err::<(), _>("connection refused"));
+        let result = breaker.call(|| {
+            let concurrent = breaker.call(|| Ok::<_, &str>(()));
+            assert!(matches!(concurrent, Err(CircuitBreakerError::OpenError)));
+            Ok::<_, &str>(())
+        });
+        assert!(result.is_ok());
+        assert!(!storage.is_open());
+    }
+
+    #[test]
+    fn circuit_breaker_can_fail_open() {
+        let mut storage = CircuitBreaker::new(failing_storage(1), 1, Duration::from_secs(60));
+        storage.set_fail_open(true);
+        let session = Session::default();
+        let result = storage.session_save(&session);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                CircuitBreakerError::StorageError(_)
+            ))
+        ));
+        assert!(storage.is_open());
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to fail open");
+        assert!(loaded.is_none());
+        let result = storage.session_destroy(session.id());
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                CircuitBreakerError::OpenError
+            ))
+        ));
+    }
+
+    #[test]
+    fn circuit_breaker_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<CircuitBreaker<MockStorage>>();
+    }
+}
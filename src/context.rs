@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Session, SessionError};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    System,
+}
+
+/// Per-user request context kept in the session under well-known keys.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RequestContext {
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub theme: Option<Theme>,
+    pub feature_flags: HashMap<String, bool>,
+}
+
+impl RequestContext {
+    pub const LOCALE_KEY: &'static str = "context.locale";
+    pub const TIMEZONE_KEY: &'static str = "context.timezone";
+    pub const THEME_KEY: &'static str = "context.theme";
+    pub const FEATURE_FLAGS_KEY: &'static str = "context.feature_flags";
+
+    pub fn from_session(session: &Session) -> Result<Self, SessionError> {
+        let context = Self {
+            locale: session.get(Self::LOCALE_KEY)?,
+            timezone: session.get(Self::TIMEZONE_KEY)?,
+            theme: session.get(Self::THEME_KEY)?,
+            feature_flags: session.get(Self::FEATURE_FLAGS_KEY)?.unwrap_or_default(),
+        };
+        Ok(context)
+    }
+
+    /// Writes the context back to the session, removing keys for unset fields.
+    pub fn store(&self, session: &mut Session) -> Result<(), SessionError> {
+        store_field(session, Self::LOCALE_KEY, self.locale.as_ref())?;
+        store_field(session, Self::TIMEZONE_KEY, self.timezone.as_ref())?;
+        store_field(session, Self::THEME_KEY, self.theme.as_ref())?;
+        let feature_flags = Some(&self.feature_flags).filter(|flags| !flags.is_empty());
+        store_field(session, Self::FEATURE_FLAGS_KEY, feature_flags)?;
+        Ok(())
+    }
+
+    pub fn feature_flag(&self, flag: &str) -> Option<bool> {
+        self.feature_flags.get(flag).copied()
+    }
+}
+
+fn store_field<T: Serialize + DeserializeOwned>(
+    session: &mut Session,
+    key: &str,
+    value: Option<&T>,
+) -> Result<(), SessionError> {
+    match value {
+        Some(value) => session.insert(key, value).map(|_| ()),
+        None => session.remove::<serde_json::Value>(key).map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_session_reads_the_well_known_keys() {
+        let mut session = Session::default();
+        session
+            .insert(RequestContext::LOCALE_KEY, &"en-GB".to_string())
+            .expect("expected insert locale to succeed");
+        session
+            .insert(RequestContext::THEME_KEY, &Theme::Dark)
+            .expect("expected insert theme to succeed");
+
+        let context =
+            RequestContext::from_session(&session).expect("expected from_session to succeed");
+        assert_eq!(context.locale, Some("en-GB".to_string()));
+        assert_eq!(context.timezone, None);
+        assert_eq!(context.theme, Some(Theme::Dark));
+        assert!(context.feature_flags.is_empty());
+    }
+
+    #[test]
+    fn store_round_trips_through_the_session() {
+        let mut session = Session::default();
+        let mut context = RequestContext {
+            timezone: Some("Europe/London".to_string()),
+            ..Default::default()
+        };
+        context.feature_flags.insert("beta".to_string(), true);
+        context
+            .store(&mut session)
+            .expect("expected store to succeed");
+
+        let stored =
+            RequestContext::from_session(&session).expect("expected from_session to succeed");
+        assert_eq!(stored, context);
+        assert_eq!(stored.feature_flag("beta"), Some(true));
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+use crate::{SessionCookie, SessionKey};
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl Display for SameSite {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// Attributes of the cookie carrying the [`SessionKey`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CookieConfig {
+    pub name: String,
+    pub domain: Option<String>,
+    pub path: String,
+    pub same_site: SameSite,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Default for CookieConfig {
+    fn default() -> Self {
+        Self {
+            name: "session".to_string(),
+            domain: None,
+            path: "/".to_string(),
+            same_site: SameSite::Lax,
+            secure: true,
+            http_only: true,
+        }
+    }
+}
+
+impl CookieConfig {
+    /// Builds a `Set-Cookie` value carrying `key` that expires after `max_age`.
+    pub fn set_cookie(&self, key: &SessionKey, max_age: Duration) -> String {
+        self.build(key.as_ref(), max_age)
+    }
+
+    /// Builds a `Set-Cookie` value that tells the client to drop the cookie.
+    pub fn remove_cookie(&self) -> String {
+        self.build("", Duration::ZERO)
+    }
+
+    /// Builds the `Set-Cookie` value for a [`SessionCookie`] action, if any.
+    pub fn header(&self, cookie: &SessionCookie) -> Option<String> {
+        match cookie {
+            SessionCookie::Set { key, max_age } => Some(self.set_cookie(key, *max_age)),
+            SessionCookie::Remove => Some(self.remove_cookie()),
+            SessionCookie::Unchanged => None,
+        }
+    }
+
+    /// Extracts the session cookie value from a `Cookie` request header.
+    /// Values that are not valid session keys are ignored.
+    pub fn parse(&self, header: &str) -> Option<SessionKey> {
+        header
+            .split(';')
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(name, _)| *name == self.name)
+            .and_then(|(_, value)| value.parse().ok())
+    }
+
+    fn build(&self, value: &str, max_age: Duration) -> String {
+        let mut cookie = format!(
+            "{}={}; Max-Age={}; Path={}",
+            self.name,
+            value,
+            max_age.as_secs(),
+            self.path
+        );
+        if let Some(domain) = &self.domain {
+            cookie.push_str(&format!("; Domain={domain}"));
+        }
+        cookie.push_str(&format!("; SameSite={}", self.same_site));
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        cookie
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_cookie_includes_the_configured_attributes() {
+        let config = CookieConfig {
+            domain: Some("example.com".to_string()),
+            ..Default::default()
+        };
+        let key = SessionKey::generate();
+        let cookie = config.set_cookie(&key, Duration::from_secs(1800));
+        let expected = format!(
+            "session={}; Max-Age=1800; Path=/; Domain=example.com; SameSite=Lax; Secure; HttpOnly",
+            key.as_ref()
+        );
+        assert_eq!(cookie, expected);
+    }
+
+    #[test]
+    fn parse_extracts_the_session_key() {
+        let config = CookieConfig::default();
+        let key = SessionKey::generate();
+        let header = format!("theme=dark; session={}; other=1", key.as_ref());
+        assert_eq!(config.parse(&header), Some(key));
+        assert_eq!(config.parse("session=not;valid"), None);
+    }
+}
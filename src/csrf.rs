@@ -0,0 +1,63 @@
+use crate::{
+    session_key::{ct_eq, random_string},
+    Session, SessionError,
+};
+
+/// Per-session CSRF tokens.
+///
+/// The token is stored in the session and rotated whenever the session key is
+/// regenerated, so a token observed before login is useless afterwards.
+pub struct Csrf;
+
+impl Csrf {
+    pub const TOKEN_KEY: &'static str = "csrf.token";
+    const TOKEN_LENGTH: usize = 32;
+
+    /// Returns the session's token, generating one if it has none.
+    pub fn issue_token(session: &mut Session) -> Result<String, SessionError> {
+        match session.get::<String>(Self::TOKEN_KEY)? {
+            Some(token) => Ok(token),
+            None => Self::rotate(session),
+        }
+    }
+
+    /// Replaces the session's token with a new one.
+    pub fn rotate(session: &mut Session) -> Result<String, SessionError> {
+        let token = random_string(Self::TOKEN_LENGTH);
+        session.insert(Self::TOKEN_KEY, &token)?;
+        Ok(token)
+    }
+
+    /// Checks `token` against the session's token in constant time.
+    pub fn verify_token(session: &Session, token: &str) -> Result<bool, SessionError> {
+        let expected = session.get::<String>(Self::TOKEN_KEY)?;
+        Ok(expected.is_some_and(|expected| ct_eq(&expected, token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_token_accepts_only_the_issued_token() {
+        let mut session = Session::default();
+        let token = Csrf::issue_token(&mut session).expect("expected issue_token to succeed");
+        let reissued = Csrf::issue_token(&mut session).expect("expected issue_token to succeed");
+        assert_eq!(token, reissued);
+
+        assert!(Csrf::verify_token(&session, &token).expect("expected verify_token to succeed"));
+        assert!(!Csrf::verify_token(&session, "forged").expect("expected verify_token to succeed"));
+    }
+
+    #[test]
+    fn regenerate_rotates_the_token() {
+        let mut session = Session::default();
+        let token = Csrf::issue_token(&mut session).expect("expected issue_token to succeed");
+        session
+            .regenerate()
+            .expect("expected regenerate to succeed");
+
+        assert!(!Csrf::verify_token(&session, &token).expect("expected verify_token to succeed"));
+    }
+}
@@ -0,0 +1,151 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Session storage with its concrete type and error erased, so the backend
+/// can be chosen at runtime, e.g. from configuration. The storage must be
+/// `Send + Sync` so it can be shared by a multithreaded server.
+///
+/// `Box<S>` and `Arc<S>` do not implement the storage traits: `Storage` and
+/// the pointer types both live in other crates, so the impls would have to
+/// come from `lushus-storage`. Pass `&S` or `&mut S` to share a storage
+/// instead, which `lushus-storage` already supports.
+pub struct DynSessionStorage {
+    storage: Box<dyn ErasedStorage + Send + Sync>,
+}
+
+impl DynSessionStorage {
+    pub fn new<S>(storage: S) -> Self
+    where
+        S: StorageRead<SessionStateTable>
+            + StorageWrite<SessionStateTable>
+            + StorageTemp<SessionStateTable>
+            + Send
+            + Sync
+            + 'static,
+        S::Error: Into<BoxError>,
+    {
+        let storage = Box::new(storage);
+        Self { storage }
+    }
+}
+
+trait ErasedStorage {
+    fn get(&self, key: &SessionKey) -> Result<Option<SessionState>, BoxError>;
+    fn exists(&self, key: &SessionKey) -> Result<bool, BoxError>;
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, BoxError>;
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, BoxError>;
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, BoxError>;
+}
+
+impl<S> ErasedStorage for S
+where
+    S: StorageRead<SessionStateTable>
+        + StorageWrite<SessionStateTable>
+        + StorageTemp<SessionStateTable>,
+    S::Error: Into<BoxError>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<SessionState>, BoxError> {
+        let value = StorageRead::get(self, key).map_err(Into::into)?;
+        Ok(value.map(Cow::into_owned))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, BoxError> {
+        StorageRead::exists(self, key).map_err(Into::into)
+    }
+
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, BoxError> {
+        StorageWrite::insert(self, key, value).map_err(Into::into)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, BoxError> {
+        StorageWrite::remove(self, key).map_err(Into::into)
+    }
+
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, BoxError> {
+        StorageTemp::ttl(self, key).map_err(Into::into)
+    }
+}
+
+impl Storage for DynSessionStorage {
+    type Error = BoxError;
+}
+
+impl StorageRead<SessionStateTable> for DynSessionStorage {
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        let value = self.storage.get(key)?;
+        Ok(value.map(Cow::Owned))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage.exists(key)
+    }
+}
+
+impl StorageWrite<SessionStateTable> for DynSessionStorage {
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        self.storage.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.storage.remove(key)
+    }
+}
+
+impl StorageTemp<SessionStateTable> for DynSessionStorage {
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage.ttl(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn dyn_session_storage_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<DynSessionStorage>();
+    }
+
+    #[test]
+    fn dyn_session_storage_forwards_to_the_wrapped_storage() {
+        let mut storage = DynSessionStorage::new(MockStorage::new());
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert_eq!(
+            loaded.map(|loaded| loaded.id().clone()),
+            Some(session.id().clone())
+        );
+
+        storage
+            .session_destroy(session.id())
+            .expect("expected destroy to succeed");
+        let exists = storage
+            .session_exists(session.id())
+            .expect("expected exists to succeed");
+        assert!(!exists);
+    }
+}
@@ -0,0 +1,308 @@
+use std::{borrow::Cow, time::Duration};
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState, SessionValue};
+
+/// The entry the encrypted state is stored under.
+const ENTRY: &str = "encrypted";
+const KEY_ID_LENGTH: usize = 4;
+const NONCE_LENGTH: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptedStorageError<StorageError> {
+    #[error("No encryption key with id {0}")]
+    UnknownKeyError(u32),
+    #[error("Failed to encrypt the session")]
+    EncryptionError,
+    #[error("Failed to decrypt the session")]
+    DecryptionError,
+    #[error(transparent)]
+    StorageError(StorageError),
+}
+
+/// Supplies the 256-bit keys [`EncryptedStorage`] encrypts sessions with.
+/// The id of the key is stored with each session, so keys can be rotated:
+/// sessions are written under [`KeyProvider::current_key_id`], and sessions
+/// written under an earlier key stay readable for as long as
+/// [`KeyProvider::key`] returns it.
+pub trait KeyProvider {
+    fn current_key_id(&self) -> u32;
+
+    fn key(&self, id: u32) -> Option<[u8; 32]>;
+}
+
+/// A single key, with id 0.
+impl KeyProvider for [u8; 32] {
+    fn current_key_id(&self) -> u32 {
+        0
+    }
+
+    fn key(&self, id: u32) -> Option<[u8; 32]> {
+        (id == 0).then_some(*self)
+    }
+}
+
+/// Wraps session storage so that sessions are stored encrypted with
+/// XChaCha20-Poly1305. The whole state is serialized and stored as a single
+/// binary entry, bound to its session key so that it cannot be moved to
+/// another one.
+///
+/// A copy of the metadata is stored in the clear, since storage applies the
+/// TTL from it and wrappers such as [`crate::ArchivingStorage`] read it.
+/// Loads only trust the encrypted copy. Sessions stored before encryption was
+/// enabled fail to load with [`EncryptedStorageError::DecryptionError`].
+pub struct EncryptedStorage<S, K> {
+    storage: S,
+    keys: K,
+}
+
+impl<S, K> EncryptedStorage<S, K> {
+    pub fn new(storage: S, keys: K) -> Self {
+        Self { storage, keys }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S, K: KeyProvider> EncryptedStorage<S, K> {
+    fn cipher<E>(&self, id: u32) -> Result<XChaCha20Poly1305, EncryptedStorageError<E>> {
+        let key = self
+            .keys
+            .key(id)
+            .ok_or(EncryptedStorageError::UnknownKeyError(id))?;
+        Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    /// Stored as the key id, the nonce and the ciphertext, in that order.
+    fn encrypt<E>(
+        &self,
+        key: &SessionKey,
+        state: &SessionState,
+    ) -> Result<SessionState, EncryptedStorageError<E>> {
+        let id = self.keys.current_key_id();
+        let plaintext =
+            serde_json::to_vec(state).map_err(|_| EncryptedStorageError::EncryptionError)?;
+        let mut nonce = [0; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let payload = Payload {
+            msg: &plaintext,
+            aad: key.as_ref().as_bytes(),
+        };
+        let ciphertext = self
+            .cipher(id)?
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| EncryptedStorageError::EncryptionError)?;
+
+        let mut value = Vec::with_capacity(KEY_ID_LENGTH + NONCE_LENGTH + ciphertext.len());
+        value.extend_from_slice(&id.to_be_bytes());
+        value.extend_from_slice(&nonce);
+        value.extend_from_slice(&ciphertext);
+        let mut stored = SessionState::default();
+        stored.set_version(state.version());
+        *stored.metadata_mut() = state.metadata().clone();
+        stored.insert(ENTRY, value);
+        Ok(stored)
+    }
+
+    fn decrypt<E>(
+        &self,
+        key: &SessionKey,
+        stored: &SessionState,
+    ) -> Result<SessionState, EncryptedStorageError<E>> {
+        let value = stored
+            .get(ENTRY)
+            .and_then(SessionValue::as_bytes)
+            .filter(|value| value.len() >= KEY_ID_LENGTH + NONCE_LENGTH)
+            .ok_or(EncryptedStorageError::DecryptionError)?;
+        let (id, value) = value.split_at(KEY_ID_LENGTH);
+        let id = u32::from_be_bytes(id.try_into().expect("key id is four bytes"));
+        let (nonce, ciphertext) = value.split_at(NONCE_LENGTH);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: key.as_ref().as_bytes(),
+        };
+        let plaintext = self
+            .cipher(id)?
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| EncryptedStorageError::DecryptionError)?;
+        serde_json::from_slice(&plaintext).map_err(|_| EncryptedStorageError::DecryptionError)
+    }
+}
+
+impl<S: Storage, K> Storage for EncryptedStorage<S, K> {
+    type Error = EncryptedStorageError<S::Error>;
+}
+
+impl<S, K> StorageRead<SessionStateTable> for EncryptedStorage<S, K>
+where
+    S: StorageRead<SessionStateTable>,
+    K: KeyProvider,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        let stored = self
+            .storage
+            .get(key)
+            .map_err(EncryptedStorageError::StorageError)?;
+        stored
+            .map(|stored| self.decrypt(key, &stored).map(Cow::Owned))
+            .transpose()
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage
+            .exists(key)
+            .map_err(EncryptedStorageError::StorageError)
+    }
+}
+
+impl<S, K> StorageWrite<SessionStateTable> for EncryptedStorage<S, K>
+where
+    S: StorageWrite<SessionStateTable>,
+    K: KeyProvider,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let stored = self.encrypt(key, value)?;
+        let previous = self
+            .storage
+            .insert(key, &stored)
+            .map_err(EncryptedStorageError::StorageError)?;
+        previous
+            .map(|previous| self.decrypt(key, &previous))
+            .transpose()
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        let previous = self
+            .storage
+            .remove(key)
+            .map_err(EncryptedStorageError::StorageError)?;
+        previous
+            .map(|previous| self.decrypt(key, &previous))
+            .transpose()
+    }
+}
+
+impl<S, K> StorageTemp<SessionStateTable> for EncryptedStorage<S, K>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage
+            .ttl(key)
+            .map_err(EncryptedStorageError::StorageError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::MockStorage, Session, SessionStorageError, SessionStorageRead,
+        SessionStorageWrite,
+    };
+
+    struct RotatedKeys {
+        oldest: u32,
+        current: u32,
+    }
+
+    impl KeyProvider for RotatedKeys {
+        fn current_key_id(&self) -> u32 {
+            self.current
+        }
+
+        fn key(&self, id: u32) -> Option<[u8; 32]> {
+            (self.oldest..=self.current)
+                .contains(&id)
+                .then_some([id as u8; 32])
+        }
+    }
+
+    #[test]
+    fn encrypted_storage_does_not_store_values_in_the_clear() {
+        let mut storage = EncryptedStorage::new(MockStorage::new(), [7; 32]);
+        let mut session = Session::default();
+        session
+            .insert("name", &"fred".to_string())
+            .expect("expected insert to succeed");
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed")
+            .expect("expected session to be present");
+        let name = loaded
+            .get::<String>("name")
+            .expect("expected get to succeed");
+        assert_eq!(name.as_deref(), Some("fred"));
+
+        let inner = storage.into_inner();
+        let stored = &inner.map[session.id()];
+        assert_eq!(stored.keys().collect::<Vec<_>>(), [ENTRY]);
+        let bytes = stored.get(ENTRY).and_then(SessionValue::as_bytes).unwrap();
+        assert!(!bytes.windows(4).any(|window| window == b"fred"));
+    }
+
+    #[test]
+    fn encrypted_storage_rejects_a_session_moved_to_another_key() {
+        let mut storage = EncryptedStorage::new(MockStorage::new(), [7; 32]);
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let other = SessionKey::generate();
+        let stored = storage.storage.map[session.id()].clone();
+        storage.storage.map.insert(other.clone(), stored);
+
+        let result = storage.session_load(&other);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                EncryptedStorageError::DecryptionError
+            ))
+        ));
+    }
+
+    #[test]
+    fn encrypted_storage_reads_sessions_written_under_an_earlier_key() {
+        let mut storage = EncryptedStorage::new(
+            MockStorage::new(),
+            RotatedKeys {
+                oldest: 0,
+                current: 0,
+            },
+        );
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+
+        storage.keys.current = 1;
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert!(loaded.is_some());
+
+        storage.keys.oldest = 1;
+        let result = storage.session_load(session.id());
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                EncryptedStorageError::UnknownKeyError(0)
+            ))
+        ));
+    }
+}
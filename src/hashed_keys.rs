@@ -0,0 +1,97 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+/// Wraps session storage so that only an HMAC of each [`SessionKey`] reaches
+/// it. A leaked copy of the storage cannot be replayed as session cookies
+/// without `secret`, which must stay the same for stored sessions to be found.
+pub struct HashedKeys<S> {
+    storage: S,
+    secret: Vec<u8>,
+}
+
+impl<S> HashedKeys<S> {
+    pub fn new(storage: S, secret: impl Into<Vec<u8>>) -> Self {
+        let secret = secret.into();
+        Self { storage, secret }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+
+    fn hash(&self, key: &SessionKey) -> SessionKey {
+        key.hmac(&self.secret)
+    }
+}
+
+impl<S: Storage> Storage for HashedKeys<S> {
+    type Error = S::Error;
+}
+
+impl<S> StorageRead<SessionStateTable> for HashedKeys<S>
+where
+    S: StorageRead<SessionStateTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.storage.get(&self.hash(key))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage.exists(&self.hash(key))
+    }
+}
+
+impl<S> StorageWrite<SessionStateTable> for HashedKeys<S>
+where
+    S: StorageWrite<SessionStateTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let key = self.hash(key);
+        self.storage.insert(&key, value)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        let key = self.hash(key);
+        self.storage.remove(&key)
+    }
+}
+
+impl<S> StorageTemp<SessionStateTable> for HashedKeys<S>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage.ttl(&self.hash(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn hashed_keys_store_sessions_under_a_hash_of_the_key() {
+        let mut storage = HashedKeys::new(MockStorage::new(), "secret");
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed")
+            .expect("expected session to be present");
+        assert_eq!(loaded.id(), session.id());
+
+        let inner = storage.into_inner();
+        assert!(!inner.map.contains_key(session.id()));
+        assert!(inner.map.contains_key(&session.id().hmac(b"secret")));
+    }
+}
@@ -0,0 +1,88 @@
+//! Observability hooks for session operations. Everything here except
+//! [`warn`] compiles to nothing unless the `tracing` or `metrics` feature is
+//! enabled.
+
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use std::time::Instant;
+
+use crate::{SessionKey, SessionState};
+
+/// A single in-flight storage operation, recorded when finished.
+pub(crate) struct Operation {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    name: &'static str,
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    start: Instant,
+    #[cfg(feature = "tracing")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Operation {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn start(name: &'static str, key: &SessionKey) -> Self {
+        Self {
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            name,
+            #[cfg(any(feature = "tracing", feature = "metrics"))]
+            start: Instant::now(),
+            #[cfg(feature = "tracing")]
+            _span: tracing::debug_span!("session", op = name, key = %key).entered(),
+        }
+    }
+
+    pub(crate) fn finish<T, E>(self, result: Result<T, E>) -> Result<T, E> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let elapsed = self.start.elapsed();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            op = self.name,
+            latency_us = elapsed.as_micros() as u64,
+            ok = result.is_ok(),
+            "session operation finished"
+        );
+        #[cfg(feature = "metrics")]
+        {
+            let outcome = if result.is_ok() { "ok" } else { "error" };
+            metrics::counter!("session_operations_total", "op" => self.name, "outcome" => outcome)
+                .increment(1);
+            metrics::histogram!("session_operation_duration_seconds", "op" => self.name)
+                .record(elapsed.as_secs_f64());
+        }
+        result
+    }
+
+    /// Like [`Operation::finish`], also recording whether the key was found.
+    pub(crate) fn finish_lookup<T, E>(self, result: Result<Option<T>, E>) -> Result<Option<T>, E> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        if let Ok(value) = &result {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(op = self.name, hit = value.is_some(), "session lookup");
+            #[cfg(feature = "metrics")]
+            {
+                let lookup = if value.is_some() { "hit" } else { "miss" };
+                metrics::counter!("session_lookups_total", "op" => self.name, "result" => lookup)
+                    .increment(1);
+            }
+        }
+        self.finish(result)
+    }
+}
+
+/// Records the size of a saved state, measured as its JSON encoding since the
+/// storage implementation's own encoding is not visible here.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_payload(state: &SessionState) {
+    #[cfg(feature = "metrics")]
+    if let Ok(payload) = serde_json::to_vec(state) {
+        metrics::histogram!("session_payload_bytes").record(payload.len() as f64);
+    }
+}
+
+/// Reports a failure that cannot be returned to the caller, through `tracing`
+/// when enabled and on stderr otherwise, so it is never lost silently.
+pub(crate) fn warn(key: &SessionKey, message: &str) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(key = %key, "{message}");
+    #[cfg(not(feature = "tracing"))]
+    eprintln!("lushus-session: {message} (session {key})");
+}
@@ -0,0 +1,200 @@
+use std::{
+    borrow::Cow,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+#[derive(Debug, thiserror::Error)]
+pub enum LayeredStorageError<CacheError, BackendError> {
+    #[error("Cache error: {0}")]
+    CacheError(CacheError),
+    #[error("Backend error: {0}")]
+    BackendError(BackendError),
+}
+
+/// Serves session loads from a local `cache` and falls through to `backend`
+/// on a miss, copying the loaded session into the cache. Saves and destroys
+/// write through to both layers.
+///
+/// The cache should expire its entries after a short TTL, since changes made
+/// to the backend by other processes are not seen by loads until it does.
+/// Existence checks always ask the backend, so a key destroyed elsewhere is
+/// never reported as taken.
+pub struct LayeredStorage<L1, L2> {
+    cache: Mutex<L1>,
+    backend: L2,
+}
+
+impl<L1, L2> LayeredStorage<L1, L2> {
+    pub fn new(cache: L1, backend: L2) -> Self {
+        let cache = Mutex::new(cache);
+        Self { cache, backend }
+    }
+
+    pub fn into_parts(self) -> (L1, L2) {
+        let cache = self
+            .cache
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        (cache, self.backend)
+    }
+
+    fn cache(&self) -> MutexGuard<'_, L1> {
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn cache_mut(&mut self) -> &mut L1 {
+        self.cache.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<L1: Storage, L2: Storage> Storage for LayeredStorage<L1, L2> {
+    type Error = LayeredStorageError<L1::Error, L2::Error>;
+}
+
+impl<L1, L2> StorageRead<SessionStateTable> for LayeredStorage<L1, L2>
+where
+    L1: StorageRead<SessionStateTable> + StorageWrite<SessionStateTable>,
+    L2: StorageRead<SessionStateTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        let cached = self
+            .cache()
+            .get(key)
+            .map_err(LayeredStorageError::CacheError)?
+            .map(Cow::into_owned);
+        if let Some(state) = cached {
+            return Ok(Some(Cow::Owned(state)));
+        }
+        let Some(state) = self
+            .backend
+            .get(key)
+            .map_err(LayeredStorageError::BackendError)?
+        else {
+            return Ok(None);
+        };
+        self.cache()
+            .insert(key, &state)
+            .map_err(LayeredStorageError::CacheError)?;
+        Ok(Some(state))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.backend
+            .exists(key)
+            .map_err(LayeredStorageError::BackendError)
+    }
+}
+
+impl<L1, L2> StorageWrite<SessionStateTable> for LayeredStorage<L1, L2>
+where
+    L1: StorageWrite<SessionStateTable>,
+    L2: StorageWrite<SessionStateTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let previous = self
+            .backend
+            .insert(key, value)
+            .map_err(LayeredStorageError::BackendError)?;
+        self.cache_mut()
+            .insert(key, value)
+            .map_err(LayeredStorageError::CacheError)?;
+        Ok(previous)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.cache_mut()
+            .remove(key)
+            .map_err(LayeredStorageError::CacheError)?;
+        self.backend
+            .remove(key)
+            .map_err(LayeredStorageError::BackendError)
+    }
+}
+
+/// The TTL always comes from the backend, which owns the session's lifetime.
+impl<L1, L2> StorageTemp<SessionStateTable> for LayeredStorage<L1, L2>
+where
+    L1: Storage,
+    L2: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.backend
+            .ttl(key)
+            .map_err(LayeredStorageError::BackendError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn load_populates_the_cache_from_the_backend() {
+        let session = Session::default();
+        let mut backend = MockStorage::new();
+        backend
+            .session_save(&session)
+            .expect("expected save to succeed");
+
+        let storage = LayeredStorage::new(MockStorage::new(), backend);
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert!(loaded.is_some());
+
+        let (cache, _) = storage.into_parts();
+        assert!(cache.map.contains_key(session.id()));
+    }
+
+    #[test]
+    fn exists_asks_the_backend() {
+        let session = Session::default();
+        let mut cache = MockStorage::new();
+        cache
+            .session_save(&session)
+            .expect("expected save to succeed");
+
+        let storage = LayeredStorage::new(cache, MockStorage::new());
+        let exists = storage
+            .session_exists(session.id())
+            .expect("expected exists to succeed");
+        assert!(!exists);
+    }
+
+    #[test]
+    fn layered_storage_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<LayeredStorage<MockStorage, MockStorage>>();
+    }
+
+    #[test]
+    fn save_and_destroy_write_through_to_both_layers() {
+        let session = Session::default();
+        let mut storage = LayeredStorage::new(MockStorage::new(), MockStorage::new());
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        {
+            let (cache, backend) = (storage.cache(), &storage.backend);
+            assert!(cache.map.contains_key(session.id()));
+            assert!(backend.map.contains_key(session.id()));
+        }
+
+        storage
+            .session_destroy(session.id())
+            .expect("expected destroy to succeed");
+        let (cache, backend) = storage.into_parts();
+        assert!(cache.map.is_empty());
+        assert!(backend.map.is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    session_model::read_session, SessionError, SessionKey, SessionModel, SessionStorageError,
+    SessionStorageRead, SessionStorageWrite,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum LazySessionError<StorageError> {
+    #[error(transparent)]
+    StorageError(#[from] SessionStorageError<StorageError>),
+    #[error(transparent)]
+    SessionError(#[from] SessionError),
+}
+
+/// A session that is not read from storage until it is first used, so
+/// requests that never touch the session cost no storage round trip.
+pub struct LazySession<S> {
+    pending: Option<Pending<S>>,
+    model: Option<SessionModel<S>>,
+}
+
+struct Pending<S> {
+    storage: S,
+    key: Option<SessionKey>,
+    duration: Duration,
+}
+
+impl<S> LazySession<S> {
+    /// Defers loading the session stored under `key`. Without a key, or if
+    /// nothing is stored under it, first use starts a new session lasting
+    /// `duration`.
+    pub fn new(storage: S, key: Option<SessionKey>, duration: Duration) -> Self {
+        let pending = Pending {
+            storage,
+            key,
+            duration,
+        };
+        Self {
+            pending: Some(pending),
+            model: None,
+        }
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.model.is_some()
+    }
+
+    /// The loaded model, or `None` if the session was never used.
+    pub fn into_model(self) -> Option<SessionModel<S>> {
+        self.model
+    }
+}
+
+impl<S> LazySession<S>
+where
+    S: SessionStorageRead,
+{
+    /// Loads the session on first call and returns it.
+    pub fn model(&mut self) -> Result<&mut SessionModel<S>, SessionStorageError<S::Error>> {
+        if let Some(pending) = &self.pending {
+            let loaded = match &pending.key {
+                Some(key) => read_session(&pending.storage, key)?,
+                None => None,
+            };
+            let pending = self.pending.take().expect("pending session was checked");
+            let model = match loaded {
+                Some((session, duration)) => {
+                    SessionModel::loaded(pending.storage, session, duration)
+                }
+                None => SessionModel::new(pending.storage, pending.duration),
+            };
+            self.model = Some(model);
+        }
+        Ok(self.model.as_mut().expect("session was loaded"))
+    }
+
+    pub fn get<T: DeserializeOwned>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<T>, LazySessionError<S::Error>> {
+        Ok(self.model()?.get(key)?)
+    }
+
+    pub fn insert<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<Option<T>, LazySessionError<S::Error>> {
+        Ok(self.model()?.insert(key, value)?)
+    }
+}
+
+impl<S> LazySession<S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    /// Saves the session if it was used.
+    pub fn save(&mut self) -> Result<(), SessionStorageError<S::Error>> {
+        match &mut self.model {
+            Some(model) => model.save(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{MockOperation, MockStorage},
+        Session,
+    };
+
+    #[test]
+    fn lazy_session_does_not_read_storage_until_used() {
+        let mut storage = MockStorage::new();
+        let mut session = Session::default();
+        session
+            .insert("name", &"fred".to_string())
+            .expect("expected insert to succeed");
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+
+        let mut lazy = LazySession::new(&storage, Some(session.id().clone()), Duration::ZERO);
+        assert!(!lazy.is_loaded());
+        assert_eq!(storage.call_count(MockOperation::Get), 0);
+
+        let name = lazy.get::<String>("name").expect("expected get to succeed");
+        assert_eq!(name.as_deref(), Some("fred"));
+        assert!(lazy.is_loaded());
+        lazy.get::<String>("name").expect("expected get to succeed");
+        assert_eq!(storage.call_count(MockOperation::Get), 1);
+    }
+
+    #[test]
+    fn lazy_session_save_is_a_no_op_when_unused() {
+        let mut storage = MockStorage::new();
+        let mut lazy = LazySession::new(&mut storage, None, Duration::from_secs(100));
+        lazy.save().expect("expected save to succeed");
+        assert!(lazy.into_model().is_none());
+        assert!(storage.map.is_empty());
+    }
+}
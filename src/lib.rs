@@ -1,10 +1,91 @@
+#[cfg(test)]
+extern crate self as lushus_session;
+
+mod archiving_storage;
+#[cfg(feature = "audit")]
+mod audit;
+mod circuit_breaker;
+mod context;
+mod cookie;
+mod csrf;
+mod dyn_session_storage;
+#[cfg(feature = "encryption")]
+mod encrypted_storage;
+#[cfg(feature = "hashed-keys")]
+mod hashed_keys;
+mod instrument;
+mod layered_storage;
+mod lazy_session;
+mod refresh;
+mod routed_storage;
 mod session;
+mod session_binding;
+mod session_entry;
+mod session_epoch;
+mod session_events;
+mod session_field;
+mod session_guard;
+mod session_handle;
 mod session_key;
+mod session_lifecycle;
+mod session_manager;
+mod session_metadata;
+mod session_migrator;
 mod session_model;
+mod session_scope;
+mod session_section;
 mod session_state;
+mod session_status;
 mod session_storage;
+mod session_user_index;
+mod sharded_storage;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
+pub use archiving_storage::{
+    ArchiveReason, ArchiveSink, ArchivingStorage, ArchivingStorageError, JsonLinesArchiveSink,
+};
+#[cfg(feature = "audit")]
+pub use audit::{AuditEvents, AuditOutcome, AuditRecord, AuditSink, Audited, JsonLinesAuditSink};
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerError};
+pub use context::{RequestContext, Theme};
+pub use cookie::{CookieConfig, SameSite};
+pub use csrf::Csrf;
+pub use dyn_session_storage::{BoxError, DynSessionStorage};
+#[cfg(feature = "encryption")]
+pub use encrypted_storage::{EncryptedStorage, EncryptedStorageError, KeyProvider};
+#[cfg(feature = "hashed-keys")]
+pub use hashed_keys::HashedKeys;
+pub use layered_storage::{LayeredStorage, LayeredStorageError};
+pub use lazy_session::{LazySession, LazySessionError};
+#[cfg(feature = "derive")]
+pub use lushus_session_derive::SessionSection;
+pub use refresh::{RefreshToken, RefreshTokenTable, SessionRefreshWrite};
+pub use routed_storage::RoutedStorage;
 pub use session::{Session, SessionError};
-pub use session_key::SessionKey;
-pub use session_model::SessionModel;
-pub use session_storage::{SessionStorageError, SessionStorageRead, SessionStorageWrite};
+pub use session_binding::{BindingCheck, SessionBinding};
+pub use session_entry::{Entry, OccupiedEntry, VacantEntry};
+pub use session_epoch::{
+    EpochCheck, EpochCheckError, EpochTable, SessionEpochRead, SessionEpochWrite,
+};
+pub use session_events::{SessionEventHandler, SessionEvents};
+pub use session_field::SessionField;
+pub use session_guard::SessionGuard;
+pub use session_handle::SessionHandle;
+pub use session_key::{SessionKey, SessionKeyError, SessionKeyRef};
+pub use session_lifecycle::{SessionLifecycle, SessionLifecycleError};
+pub use session_manager::{SessionCookie, SessionManager};
+pub use session_metadata::SessionMetadata;
+pub use session_migrator::SessionMigrator;
+pub use session_model::{ExpirationPolicy, SaveConflictPolicy, SaveMode, SessionModel};
+pub use session_scope::SessionScope;
+pub use session_section::SessionSection;
+pub use session_state::{SessionState, SessionValue};
+pub use session_status::SessionStatus;
+pub use session_storage::{
+    SessionStateTable, SessionStorageError, SessionStorageRead, SessionStorageWrite,
+};
+pub use session_user_index::{
+    SessionUserIndexRead, SessionUserIndexWrite, UserIndex, UserSessionsTable,
+};
+pub use sharded_storage::ShardedStorage;
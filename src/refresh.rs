@@ -0,0 +1,254 @@
+use std::{
+    borrow::Cow,
+    time::{Duration, SystemTime},
+};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite, Table};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    session_key::{random_string, sha256_hex},
+    SessionKey, SessionStorageError, SessionStorageRead, SessionStorageWrite,
+};
+
+const TOKEN_LENGTH: usize = 64;
+
+/// What is stored for a refresh token: the session it was issued for and when
+/// it expires. Tokens themselves are random and are only stored hashed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RefreshToken {
+    session: SessionKey,
+    expires_at: SystemTime,
+}
+
+impl RefreshToken {
+    pub fn session(&self) -> &SessionKey {
+        &self.session
+    }
+
+    pub fn expires_at(&self) -> SystemTime {
+        self.expires_at
+    }
+
+    /// The time left until the token expires, for storage to expire the entry
+    /// with.
+    pub fn ttl(&self) -> Duration {
+        let ttl = self.expires_at.duration_since(SystemTime::now());
+        ttl.unwrap_or(Duration::ZERO)
+    }
+}
+
+/// Maps hashes of refresh tokens to the session they were issued for.
+/// Storage should expire entries after [`RefreshToken::ttl`] and report the
+/// time left through [`StorageTemp`].
+pub struct RefreshTokenTable {}
+
+impl Table for RefreshTokenTable {
+    type Key = String;
+    type OwnedKey = Self::Key;
+    type Value = RefreshToken;
+    type OwnedValue = Self::Value;
+}
+
+pub trait SessionRefreshWrite
+where
+    Self: Storage,
+{
+    /// Issues an opaque token for `session` that can be refreshed within
+    /// `ttl`, for clients that cannot rely on cookie expiry.
+    fn issue_refresh_token(
+        &mut self,
+        session: &SessionKey,
+        ttl: Duration,
+    ) -> Result<String, SessionStorageError<Self::Error>>;
+
+    /// Exchanges `token` for a new key to its session and a new token valid
+    /// for `ttl`. The session is moved to the new key and `token` is
+    /// consumed; a token is only consumed once it has been checked, so a
+    /// failed storage call leaves it usable. Returns `None` if the token is
+    /// unknown or expired, or the session no longer exists. Fails with
+    /// [`SessionStorageError::LoggedOutError`] if the session was logged out.
+    ///
+    /// Sessions from an earlier epoch are only rejected when the storage is
+    /// wrapped in [`crate::EpochCheck`].
+    fn refresh(
+        &mut self,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<(SessionKey, String)>, SessionStorageError<Self::Error>>;
+}
+
+impl<S> SessionRefreshWrite for S
+where
+    S: StorageRead<RefreshTokenTable>
+        + StorageWrite<RefreshTokenTable>
+        + StorageTemp<RefreshTokenTable>
+        + SessionStorageRead
+        + SessionStorageWrite,
+{
+    fn issue_refresh_token(
+        &mut self,
+        session: &SessionKey,
+        ttl: Duration,
+    ) -> Result<String, SessionStorageError<Self::Error>> {
+        let token = random_string(TOKEN_LENGTH);
+        let refresh = RefreshToken {
+            session: session.clone(),
+            expires_at: SystemTime::now() + ttl,
+        };
+        StorageWrite::<RefreshTokenTable>::insert(self, &sha256_hex(token.as_bytes()), &refresh)?;
+        Ok(token)
+    }
+
+    fn refresh(
+        &mut self,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<(SessionKey, String)>, SessionStorageError<Self::Error>> {
+        let key = sha256_hex(token.as_bytes());
+        let refresh = StorageRead::<RefreshTokenTable>::get(self, &key)?;
+        let Some(refresh) = refresh.map(Cow::into_owned) else {
+            return Ok(None);
+        };
+        let remaining = StorageTemp::<RefreshTokenTable>::ttl(self, &key)?;
+        if remaining.is_zero() || refresh.expires_at <= SystemTime::now() {
+            StorageWrite::<RefreshTokenTable>::remove(self, &key)?;
+            return Ok(None);
+        }
+        let id = refresh.session;
+        let Some(mut session) = self.session_load(&id)? else {
+            StorageWrite::<RefreshTokenTable>::remove(self, &key)?;
+            return Ok(None);
+        };
+        if session.is_tombstone() {
+            StorageWrite::<RefreshTokenTable>::remove(self, &key)?;
+            return Err(SessionStorageError::LoggedOutError(id));
+        }
+        // Only the caller that removes the token gets to use it.
+        if StorageWrite::<RefreshTokenTable>::remove(self, &key)?.is_none() {
+            return Ok(None);
+        }
+        let key = session
+            .regenerate()
+            .map_err(|e| SessionStorageError::from_regenerate(&id, e))?
+            .clone();
+        self.session_save(&session)?;
+        self.session_destroy(&id)?;
+        let token = self.issue_refresh_token(&key, ttl)?;
+        Ok(Some((key, token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{MockOperation, MockStorage, MockStorageError},
+        EpochCheck, Session, SessionEpochWrite,
+    };
+
+    #[test]
+    fn refresh_moves_the_session_and_consumes_the_token() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let ttl = Duration::from_secs(3600);
+        let token = storage
+            .issue_refresh_token(session.id(), ttl)
+            .expect("expected issue_refresh_token to succeed");
+
+        let (key, next) = storage
+            .refresh(&token, ttl)
+            .expect("expected refresh to succeed")
+            .expect("expected token to be valid");
+        assert_ne!(&key, session.id());
+        assert_ne!(next, token);
+        assert!(storage.map.contains_key(&key));
+        assert!(!storage.map.contains_key(session.id()));
+        assert!(!storage.refresh_tokens.contains_key(&token));
+        assert_eq!(storage.refresh_tokens.len(), 1);
+
+        let reused = storage
+            .refresh(&token, ttl)
+            .expect("expected refresh to succeed");
+        assert!(reused.is_none());
+    }
+
+    #[test]
+    fn refresh_rejects_expired_tokens() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let token = storage
+            .issue_refresh_token(session.id(), Duration::ZERO)
+            .expect("expected issue_refresh_token to succeed");
+
+        let refreshed = storage
+            .refresh(&token, Duration::ZERO)
+            .expect("expected refresh to succeed");
+        assert!(refreshed.is_none());
+    }
+
+    #[test]
+    fn refresh_rejects_logged_out_and_invalidated_sessions() {
+        let mut storage = MockStorage::new();
+        let mut session = Session::default();
+        session.tombstone(Duration::from_secs(60));
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let ttl = Duration::from_secs(3600);
+        let token = storage
+            .issue_refresh_token(session.id(), ttl)
+            .expect("expected issue_refresh_token to succeed");
+        let refreshed = storage.refresh(&token, ttl);
+        assert!(matches!(
+            refreshed,
+            Err(SessionStorageError::LoggedOutError(key)) if &key == session.id()
+        ));
+
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let token = storage
+            .issue_refresh_token(session.id(), ttl)
+            .expect("expected issue_refresh_token to succeed");
+        storage
+            .invalidate_all()
+            .expect("expected invalidate_all to succeed");
+        let refreshed = EpochCheck::new(&mut storage)
+            .refresh(&token, ttl)
+            .expect("expected refresh to succeed");
+        assert!(refreshed.is_none());
+        assert!(storage.map.contains_key(session.id()));
+    }
+
+    #[test]
+    fn refresh_keeps_the_token_when_loading_the_session_fails() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let ttl = Duration::from_secs(3600);
+        let token = storage
+            .issue_refresh_token(session.id(), ttl)
+            .expect("expected issue_refresh_token to succeed");
+        assert!(!token.contains(session.id().as_ref()));
+
+        let error = MockStorageError("connection reset".to_string());
+        storage.fail_next(MockOperation::Get, error);
+        let refreshed = storage.refresh(&token, ttl);
+        assert!(refreshed.is_err());
+
+        let refreshed = storage
+            .refresh(&token, ttl)
+            .expect("expected refresh to succeed");
+        assert!(refreshed.is_some());
+    }
+}
@@ -0,0 +1,101 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+/// Sends loads, existence checks and TTL lookups to `replica`, and saves and
+/// destroys to `primary`.
+///
+/// Reads may lag behind writes by the replication delay, so a session saved
+/// in one request may not be visible to the next. Optimistic locking reads
+/// through the replica too and can miss a concurrent save.
+pub struct RoutedStorage<P, R> {
+    primary: P,
+    replica: R,
+}
+
+impl<P, R> RoutedStorage<P, R> {
+    pub fn new(primary: P, replica: R) -> Self {
+        Self { primary, replica }
+    }
+
+    pub fn into_parts(self) -> (P, R) {
+        (self.primary, self.replica)
+    }
+}
+
+impl<P: Storage, R: Storage<Error = P::Error>> Storage for RoutedStorage<P, R> {
+    type Error = P::Error;
+}
+
+impl<P, R> StorageRead<SessionStateTable> for RoutedStorage<P, R>
+where
+    P: Storage,
+    R: StorageRead<SessionStateTable, Error = P::Error>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.replica.get(key)
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.replica.exists(key)
+    }
+}
+
+impl<P, R> StorageWrite<SessionStateTable> for RoutedStorage<P, R>
+where
+    P: StorageWrite<SessionStateTable>,
+    R: Storage<Error = P::Error>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        self.primary.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.primary.remove(key)
+    }
+}
+
+impl<P, R> StorageTemp<SessionStateTable> for RoutedStorage<P, R>
+where
+    P: Storage,
+    R: StorageTemp<SessionStateTable, Error = P::Error>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.replica.ttl(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn routed_storage_reads_from_the_replica_and_writes_to_the_primary() {
+        let session = Session::default();
+        let mut replica = MockStorage::new();
+        replica
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let mut storage = RoutedStorage::new(MockStorage::new(), replica);
+
+        let exists = storage
+            .session_exists(session.id())
+            .expect("expected exists to succeed");
+        assert!(exists);
+
+        let other = Session::default();
+        storage
+            .session_save(&other)
+            .expect("expected save to succeed");
+        let (primary, replica) = storage.into_parts();
+        assert!(primary.map.contains_key(other.id()));
+        assert!(!replica.map.contains_key(other.id()));
+    }
+}
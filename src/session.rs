@@ -1,6 +1,18 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, SystemTime},
+};
+
 use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{session_state::SessionState, SessionKey};
+use crate::{
+    csrf::Csrf,
+    session_binding::{BindingCheck, SessionBinding},
+    session_entry::Entry,
+    session_scope::SessionScope,
+    session_state::{SessionState, SessionValue},
+    SessionField, SessionKey, SessionLifecycle, SessionMigrator, SessionSection, SessionStatus,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum SessionError {
@@ -12,17 +24,21 @@ pub enum SessionError {
     DeserializationError(String, String),
     #[error("Session is invalid: {0}")]
     InvalidSessionError(String),
+    #[error("Scope name \"{0}\" must not contain '.'")]
+    InvalidScopeError(String),
 }
 
 #[derive(Default)]
 pub struct Session {
     id: SessionKey,
     state: SessionState,
+    status: SessionStatus,
 }
 
 impl Session {
     pub fn new(id: SessionKey, state: SessionState) -> Self {
-        Session { id, state }
+        let status = SessionStatus::Unchanged;
+        Session { id, state, status }
     }
 
     pub fn id(&self) -> &SessionKey {
@@ -33,40 +49,517 @@ impl Session {
         &self.state
     }
 
+    pub fn created_at(&self) -> SystemTime {
+        self.state.metadata().created_at()
+    }
+
+    pub fn last_accessed(&self) -> Option<SystemTime> {
+        self.state.metadata().last_accessed()
+    }
+
+    pub fn access_count(&self) -> u64 {
+        self.state.metadata().access_count()
+    }
+
+    /// Records a load in the metadata. This does not mark the session as
+    /// changed, see [`crate::SessionModel::set_touch_interval`].
+    pub(crate) fn record_access(&mut self) {
+        self.state.metadata_mut().record_access();
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.state.metadata().revision()
+    }
+
+    pub(crate) fn set_revision(&mut self, revision: u64) {
+        self.state.metadata_mut().set_revision(revision);
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.state.metadata().user_id()
+    }
+
+    pub fn set_user_id(&mut self, user_id: Option<String>) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        self.state.metadata_mut().set_user_id(user_id);
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    pub fn lifecycle(&self) -> SessionLifecycle {
+        self.state.metadata().lifecycle()
+    }
+
+    /// Moves the session to `lifecycle`. Elevation lasts until
+    /// `elevated_until` and is dropped by any other lifecycle.
+    pub(crate) fn set_lifecycle(
+        &mut self,
+        lifecycle: SessionLifecycle,
+        elevated_until: Option<SystemTime>,
+    ) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        let elevated_until = elevated_until.filter(|_| lifecycle == SessionLifecycle::Elevated);
+        let metadata = self.state.metadata_mut();
+        metadata.set_lifecycle(lifecycle);
+        metadata.set_elevated_until(elevated_until);
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    pub(crate) fn base_timeout(&self) -> Option<Duration> {
+        self.state.metadata().base_timeout()
+    }
+
+    pub(crate) fn set_base_timeout(&mut self, timeout: Option<Duration>) {
+        self.state.metadata_mut().set_base_timeout(timeout);
+    }
+
+    /// Whether the session is elevated and its elevation has not run out.
+    pub fn is_elevated(&self) -> bool {
+        let metadata = self.state.metadata();
+        metadata.lifecycle() == SessionLifecycle::Elevated
+            && metadata
+                .elevated_until()
+                .is_some_and(|until| SystemTime::now() < until)
+    }
+
+    pub fn binding(&self) -> Option<&SessionBinding> {
+        self.state.metadata().binding()
+    }
+
+    /// Binds the session to the client described by `binding`.
+    pub fn bind(&mut self, binding: SessionBinding) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        self.state.metadata_mut().set_binding(binding);
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    pub fn verify_binding(&self, fingerprint: &SessionBinding) -> BindingCheck {
+        self.binding()
+            .map_or(BindingCheck::Unbound, |binding| binding.check(fingerprint))
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.state.metadata().timeout()
+    }
+
+    /// Sets the TTL storage applies to the next write only, see
+    /// [`crate::SessionMetadata::ttl`].
+    pub(crate) fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.state.metadata_mut().set_ttl(ttl);
+    }
+
+    /// Records a TTL for this session that storage should use instead of its
+    /// default.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        self.state.metadata_mut().set_timeout(timeout);
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.state.keys()
+    }
+
+    /// Iterates over the entries with their values in serialized form.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SessionValue)> {
+        self.state.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.status
+    }
+
+    pub(crate) fn set_status(&mut self, status: SessionStatus) {
+        self.status = status;
+    }
+
+    pub(crate) fn set_version(&mut self, version: u32) {
+        self.state.set_version(version);
+    }
+
+    fn ensure_writable(&self) -> Result<(), SessionError> {
+        match self.status {
+            SessionStatus::Destroyed => Err(SessionError::SessionDestroyedError),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the session key with a freshly generated one, keeping the state.
+    /// Any CSRF token is rotated along with the key.
+    pub fn regenerate(&mut self) -> Result<&SessionKey, SessionError> {
+        self.ensure_writable()?;
+        self.rotate_key();
+        if self.state.get(Csrf::TOKEN_KEY).is_some() {
+            Csrf::rotate(self)?;
+        }
+        Ok(&self.id)
+    }
+
+    /// Swaps in a freshly generated key without recording the current one as
+    /// replaced, for a new session whose key collided with another session's.
+    pub(crate) fn replace_key(&mut self) {
+        self.id = SessionKey::generate();
+    }
+
+    fn rotate_key(&mut self) {
+        let previous = std::mem::take(&mut self.id);
+        let metadata = self.state.metadata_mut();
+        // Until the session is stored again, the key it replaces is the one
+        // it was stored under, not any key generated since.
+        if metadata.replaces().is_none() {
+            metadata.set_replaces(Some(previous));
+        }
+        self.status = SessionStatus::Changed;
+    }
+
+    /// Forgets the key the session had before it was regenerated, once the
+    /// session is stored under its new key or is no longer a rotation.
+    pub(crate) fn clear_replaces(&mut self) {
+        self.state.metadata_mut().set_replaces(None);
+    }
+
+    /// Marks the session as changed if its metadata was recreated on load,
+    /// so the recreated creation time is stored.
+    pub(crate) fn store_legacy_metadata(&mut self) {
+        let metadata = self.state.metadata_mut();
+        if metadata.is_legacy() {
+            metadata.clear_legacy();
+            self.status = SessionStatus::Changed;
+        }
+    }
+
+    pub fn is_tombstone(&self) -> bool {
+        self.state.metadata().is_tombstone()
+    }
+
+    /// Replaces all state and metadata with a tombstone that storage keeps for
+    /// `grace`.
+    pub(crate) fn tombstone(&mut self, grace: Duration) {
+        self.state = SessionState::default();
+        let metadata = self.state.metadata_mut();
+        metadata.set_tombstone();
+        metadata.set_timeout(grace);
+        self.status = SessionStatus::Changed;
+    }
+
+    /// Removes all state while keeping the session key. The session is no
+    /// longer authenticated or bound afterwards.
+    pub fn clear(&mut self) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        self.state.clear();
+        self.state.metadata_mut().reset_identity();
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    /// Removes all state and generates a new session key. Unlike
+    /// [`Session::clear`], this also revives a destroyed session.
+    pub fn renew(&mut self) -> &SessionKey {
+        self.state.clear();
+        self.state.metadata_mut().reset_identity();
+        self.rotate_key();
+        &self.id
+    }
+
+    /// Upgrades the state to the migrator's current version, marking the session
+    /// as changed if any migration ran.
+    pub fn migrate(&mut self, migrator: &SessionMigrator) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        if migrator.migrate(&mut self.state)? {
+            self.status = SessionStatus::Changed;
+        }
+        Ok(())
+    }
+
     pub fn insert<T: Serialize + DeserializeOwned>(
         &mut self,
         key: &str,
         value: &T,
     ) -> Result<Option<T>, SessionError> {
-        let insert = serde_json::to_string(value)
+        self.ensure_writable()?;
+        let insert = serde_json::to_value(value)
             .map_err(|e| SessionError::SerializationError(key.to_string(), e.to_string()))?;
-        let previous = self
-            .state
+        self.status = SessionStatus::Changed;
+        self.state
             .insert(key, insert)
-            .as_deref()
-            .map(serde_json::from_str)
+            .map(|v| decode(key, &v))
             .transpose()
-            .map_err(|e| SessionError::DeserializationError(key.to_string(), e.to_string()));
-        previous
     }
 
     pub fn remove<T: DeserializeOwned>(&mut self, key: &str) -> Result<Option<T>, SessionError> {
-        self.state
-            .remove(key)
-            .map(|v| serde_json::from_str(&v))
-            .transpose()
-            .map_err(|e| SessionError::DeserializationError(key.to_string(), e.to_string()))
+        let removed = self.remove_value(key)?;
+        removed.map(|v| decode(key, &v)).transpose()
+    }
+
+    /// Removes the value under `key` in whatever form it is stored.
+    pub(crate) fn remove_value(&mut self, key: &str) -> Result<Option<SessionValue>, SessionError> {
+        self.ensure_writable()?;
+        let removed = self.state.remove(key);
+        if removed.is_some() {
+            self.status = SessionStatus::Changed;
+        }
+        Ok(removed)
     }
 
     pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
-        self.state
-            .get(key)
-            .map(|v| serde_json::from_str(v))
-            .transpose()
-            .map_err(|e| SessionError::DeserializationError(key.to_string(), e.to_string()))
+        self.state.get(key).map(|v| decode(key, v)).transpose()
+    }
+
+    /// Reads several keys at once, returning their values in the order of
+    /// `keys`.
+    pub fn get_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&str],
+    ) -> Result<Vec<Option<T>>, SessionError> {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Inserts every entry of `entries`. All values are serialized before any
+    /// is stored, so on error the session is left unchanged.
+    pub fn extend<K, T, I>(&mut self, entries: I) -> Result<(), SessionError>
+    where
+        K: AsRef<str>,
+        T: Serialize,
+        I: IntoIterator<Item = (K, T)>,
+    {
+        self.ensure_writable()?;
+        let entries = entries
+            .into_iter()
+            .map(|(key, value)| {
+                let key = key.as_ref();
+                serde_json::to_value(&value)
+                    .map(|value| (key.to_string(), value))
+                    .map_err(|e| SessionError::SerializationError(key.to_string(), e.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if entries.is_empty() {
+            return Ok(());
+        }
+        for (key, value) in entries {
+            self.state.insert(&key, value);
+        }
+        self.status = SessionStatus::Changed;
+        Ok(())
+    }
+
+    /// Stores raw bytes under `key` without serializing them.
+    pub fn insert_bytes(&mut self, key: &str, value: Vec<u8>) -> Result<(), SessionError> {
+        self.ensure_writable()?;
+        self.status = SessionStatus::Changed;
+        self.state.insert(key, value);
+        Ok(())
+    }
+
+    pub fn remove_bytes(&mut self, key: &str) -> Result<Option<Vec<u8>>, SessionError> {
+        self.ensure_writable()?;
+        match self.state.get(key) {
+            None => Ok(None),
+            Some(SessionValue::Json(_)) => Err(not_binary(key)),
+            Some(SessionValue::Binary(_)) => {
+                self.status = SessionStatus::Changed;
+                match self.state.remove(key) {
+                    Some(SessionValue::Binary(value)) => Ok(Some(value)),
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    pub fn get_bytes(&self, key: &str) -> Result<Option<&[u8]>, SessionError> {
+        match self.state.get(key) {
+            None => Ok(None),
+            Some(SessionValue::Json(_)) => Err(not_binary(key)),
+            Some(SessionValue::Binary(value)) => Ok(Some(value)),
+        }
+    }
+
+    /// Returns a view whose keys are namespaced under `name`, which must not
+    /// contain '.'.
+    pub fn scope(&mut self, name: &str) -> Result<SessionScope<'_>, SessionError> {
+        SessionScope::new(self, name)
+    }
+
+    pub fn entry<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+    ) -> Result<Entry<'_, T>, SessionError> {
+        Entry::new(self, key)
+    }
+
+    pub fn insert_typed<F: SessionField>(
+        &mut self,
+        value: &F::Value,
+    ) -> Result<Option<F::Value>, SessionError> {
+        self.insert(F::KEY, value)
+    }
+
+    pub fn remove_typed<F: SessionField>(&mut self) -> Result<Option<F::Value>, SessionError> {
+        self.remove(F::KEY)
+    }
+
+    pub fn get_typed<F: SessionField>(&self) -> Result<Option<F::Value>, SessionError> {
+        self.get(F::KEY)
+    }
+
+    /// Reads the fields of `T` stored under its prefix, returning `None` if
+    /// none are present.
+    pub fn load_section<T: SessionSection>(&self) -> Result<Option<T>, SessionError> {
+        let prefix = format!("{}.", T::PREFIX);
+        let mut fields = serde_json::Map::new();
+        for (key, value) in self.state.iter() {
+            if let Some(field) = key.strip_prefix(&prefix) {
+                fields.insert(field.to_string(), decode(key, value)?);
+            }
+        }
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_value(fields.into())
+            .map(Some)
+            .map_err(|e| SessionError::DeserializationError(T::PREFIX.to_string(), e.to_string()))
+    }
+
+    /// Stores each field of `section` under its own key.
+    pub fn store_section<T: SessionSection>(&mut self, section: &T) -> Result<(), SessionError> {
+        for (field, value) in fields(T::PREFIX, section)? {
+            self.insert(&format!("{}.{field}", T::PREFIX), &value)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the whole session into `T`, one field per key. Binary values are
+    /// skipped. Use [`Session::load_section`] to read a prefixed subset.
+    pub fn hydrate<T: DeserializeOwned>(&self) -> Result<T, SessionError> {
+        let mut fields = serde_json::Map::new();
+        for (key, value) in self.state.iter() {
+            if let SessionValue::Json(value) = value {
+                fields.insert(key.to_string(), value.clone());
+            }
+        }
+        serde_json::from_value(fields.into()).map_err(|e| {
+            SessionError::DeserializationError(WHOLE_SESSION.to_string(), e.to_string())
+        })
+    }
+
+    /// Stores each field of `value` under its own key, leaving other keys
+    /// untouched.
+    pub fn dehydrate<T: Serialize>(&mut self, value: &T) -> Result<(), SessionError> {
+        for (field, value) in fields(WHOLE_SESSION, value)? {
+            self.insert(&field, &value)?;
+        }
+        Ok(())
+    }
+
+    /// Adds `value` to the set stored under `key`, returning `false` if it was
+    /// already a member. Sets are stored as JSON arrays.
+    pub fn set_add<T: Serialize + DeserializeOwned + PartialEq>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<bool, SessionError> {
+        let mut members = self.get::<Vec<T>>(key)?.unwrap_or_default();
+        if members.contains(&value) {
+            return Ok(false);
+        }
+        members.push(value);
+        self.insert(key, &members)?;
+        Ok(true)
+    }
+
+    /// Removes `value` from the set stored under `key`, returning `false` if it
+    /// was not a member.
+    pub fn set_remove<T: Serialize + DeserializeOwned + PartialEq>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<bool, SessionError> {
+        let mut members = self.get::<Vec<T>>(key)?.unwrap_or_default();
+        let len = members.len();
+        members.retain(|member| member != value);
+        if members.len() == len {
+            return Ok(false);
+        }
+        self.insert(key, &members)?;
+        Ok(true)
+    }
+
+    pub fn set_contains<T: DeserializeOwned + PartialEq>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<bool, SessionError> {
+        let members = self.get::<Vec<T>>(key)?.unwrap_or_default();
+        Ok(members.contains(value))
+    }
+
+    /// Appends `value` to the list stored under `key`, returning the new
+    /// length. Lists are stored as JSON arrays.
+    pub fn push<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value: T,
+    ) -> Result<usize, SessionError> {
+        let mut items = self.get::<Vec<T>>(key)?.unwrap_or_default();
+        items.push(value);
+        self.insert(key, &items)?;
+        Ok(items.len())
+    }
+
+    /// Removes and returns the first value of the list stored under `key`, so
+    /// together with [`Session::push`] the list works as a queue.
+    pub fn pop_front<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<T>, SessionError> {
+        let mut items = self.get::<VecDeque<T>>(key)?.unwrap_or_default();
+        let Some(first) = items.pop_front() else {
+            return Ok(None);
+        };
+        self.insert(key, &items)?;
+        Ok(Some(first))
     }
 }
 
+fn decode<T: DeserializeOwned>(key: &str, value: &SessionValue) -> Result<T, SessionError> {
+    match value {
+        SessionValue::Json(value) => T::deserialize(value)
+            .map_err(|e| SessionError::DeserializationError(key.to_string(), e.to_string())),
+        SessionValue::Binary(_) => Err(SessionError::DeserializationError(
+            key.to_string(),
+            "value is binary".to_string(),
+        )),
+    }
+}
+
+// Reported as the key in errors from whole-struct reads and writes.
+const WHOLE_SESSION: &str = "*";
+
+fn fields<T: Serialize>(
+    name: &str,
+    value: &T,
+) -> Result<serde_json::Map<String, serde_json::Value>, SessionError> {
+    let serialization_error = |e: String| SessionError::SerializationError(name.to_string(), e);
+    match serde_json::to_value(value).map_err(|e| serialization_error(e.to_string()))? {
+        serde_json::Value::Object(fields) => Ok(fields),
+        _ => Err(serialization_error("value is not a struct".to_string())),
+    }
+}
+
+fn not_binary(key: &str) -> SessionError {
+    SessionError::DeserializationError(key.to_string(), "value is not binary".to_string())
+}
+
 impl From<Session> for SessionState {
     fn from(session: Session) -> Self {
         session.state
@@ -91,6 +584,10 @@ mod tests {
         password: String,
     }
 
+    impl SessionSection for User {
+        const PREFIX: &'static str = "user";
+    }
+
     #[test]
     fn insert_inserts_the_given_key_and_value() {
         let mut session = Session::default();
@@ -143,4 +640,317 @@ mod tests {
         assert_eq!(user.username, "brandon".to_string());
         assert_eq!(user.password, "hunter2".to_string());
     }
+
+    struct CurrentUser;
+
+    impl SessionField for CurrentUser {
+        const KEY: &'static str = "user";
+        type Value = User;
+    }
+
+    #[test]
+    fn get_typed_returns_the_value_for_the_field() {
+        let mut session = Session::default();
+        let user = User {
+            username: "brandon".to_string(),
+            password: "hunter2".to_string(),
+        };
+        session
+            .insert_typed::<CurrentUser>(&user)
+            .expect("expected insert_typed to succeed");
+
+        let stored = session
+            .get::<User>("user")
+            .expect("expected get \"user\" to succeed");
+        assert_eq!(stored.as_ref(), Some(&user));
+        let typed = session
+            .get_typed::<CurrentUser>()
+            .expect("expected get_typed to succeed");
+        assert_eq!(typed, Some(user));
+    }
+
+    #[test]
+    fn set_add_adds_each_member_once() {
+        let mut session = Session::default();
+        let added = session
+            .set_add("seen", "banner".to_string())
+            .expect("expected set_add \"seen\" to succeed");
+        assert!(added, "expected first set_add to add the member");
+        let added = session
+            .set_add("seen", "banner".to_string())
+            .expect("expected set_add \"seen\" to succeed");
+        assert!(!added, "expected second set_add to be a no-op");
+
+        let members = session
+            .get::<Vec<String>>("seen")
+            .expect("expected get \"seen\" to succeed")
+            .expect("expected get \"seen\" to return members");
+        assert_eq!(members, vec!["banner".to_string()]);
+    }
+
+    #[test]
+    fn set_remove_removes_the_member() {
+        let mut session = Session::default();
+        session
+            .set_add("seen", 1)
+            .expect("expected set_add \"seen\" to succeed");
+        session
+            .set_add("seen", 2)
+            .expect("expected set_add \"seen\" to succeed");
+
+        let removed = session
+            .set_remove("seen", &1)
+            .expect("expected set_remove \"seen\" to succeed");
+        assert!(removed);
+        let contains = session
+            .set_contains("seen", &1)
+            .expect("expected set_contains \"seen\" to succeed");
+        assert!(!contains, "expected 1 to have been removed");
+        let contains = session
+            .set_contains("seen", &2)
+            .expect("expected set_contains \"seen\" to succeed");
+        assert!(contains, "expected 2 to remain");
+    }
+
+    #[test]
+    fn iter_returns_every_entry() {
+        let mut session = Session::default();
+        assert!(session.is_empty());
+        session
+            .insert("id", &"abc".to_string())
+            .expect("expected insert \"id\" to succeed");
+        session
+            .insert("count", &1)
+            .expect("expected insert \"count\" to succeed");
+        assert_eq!(session.len(), 2);
+
+        let mut keys = session.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["count", "id"]);
+        let mut entries = session
+            .iter()
+            .map(|(key, value)| (key, value.as_json().map(ToString::to_string)))
+            .collect::<Vec<_>>();
+        entries.sort();
+        let expected = vec![
+            ("count", Some("1".to_string())),
+            ("id", Some("\"abc\"".to_string())),
+        ];
+        assert_eq!(entries, expected);
+    }
+
+    #[test]
+    fn get_bytes_returns_the_stored_bytes() {
+        let mut session = Session::default();
+        session
+            .insert_bytes("token", vec![0, 159, 146, 150])
+            .expect("expected insert_bytes \"token\" to succeed");
+
+        let bytes = session
+            .get_bytes("token")
+            .expect("expected get_bytes \"token\" to succeed");
+        assert_eq!(bytes, Some(&[0, 159, 146, 150][..]));
+        let result = session.get::<String>("token");
+        assert!(matches!(
+            result,
+            Err(SessionError::DeserializationError(..))
+        ));
+    }
+
+    #[test]
+    fn insert_marks_the_session_as_changed() {
+        let mut session = Session::default();
+        assert_eq!(session.status(), SessionStatus::Unchanged);
+        session
+            .insert("id", &"abc".to_string())
+            .expect("expected insert \"id\" to succeed");
+        assert_eq!(session.status(), SessionStatus::Changed);
+    }
+
+    #[test]
+    fn insert_fails_once_the_session_is_destroyed() {
+        let mut session = Session::default();
+        session.set_status(SessionStatus::Destroyed);
+        let result = session.insert("id", &"abc".to_string());
+        assert!(matches!(result, Err(SessionError::SessionDestroyedError)));
+    }
+
+    #[test]
+    fn clear_removes_all_state_and_keeps_the_key() {
+        let mut session = Session::default();
+        session
+            .insert("id", &"abc".to_string())
+            .expect("expected insert \"id\" to succeed");
+        let id = session.id().clone();
+
+        session.clear().expect("expected clear to succeed");
+        assert_eq!(session.id(), &id);
+        let value = session
+            .get::<String>("id")
+            .expect("expected get \"id\" to succeed");
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn renew_removes_all_state_and_replaces_the_key() {
+        let mut session = Session::default();
+        session
+            .insert("id", &"abc".to_string())
+            .expect("expected insert \"id\" to succeed");
+        session.set_status(SessionStatus::Destroyed);
+        let previous = session.id().clone();
+
+        let id = session.renew().clone();
+        assert_ne!(id, previous);
+        assert_eq!(session.status(), SessionStatus::Changed);
+        let value = session
+            .get::<String>("id")
+            .expect("expected get \"id\" to succeed");
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn regenerate_replaces_the_key_and_keeps_the_state() {
+        let mut session = Session::default();
+        session
+            .insert("id", &"abc".to_string())
+            .expect("expected insert \"id\" to succeed");
+        let previous = session.id().clone();
+
+        let id = session
+            .regenerate()
+            .expect("expected regenerate to succeed")
+            .clone();
+        assert_ne!(id, previous);
+        assert_eq!(session.id(), &id);
+        let value = session
+            .get::<String>("id")
+            .expect("expected get \"id\" to succeed");
+        assert_eq!(value, Some("abc".to_string()));
+    }
+
+    #[test]
+    fn regenerate_remembers_the_first_replaced_key() {
+        let mut session = Session::default();
+        let previous = session.id().clone();
+        session
+            .regenerate()
+            .expect("expected regenerate to succeed");
+        session
+            .regenerate()
+            .expect("expected regenerate to succeed");
+
+        assert_eq!(session.state().metadata().replaces(), Some(&previous));
+    }
+
+    #[test]
+    fn regenerate_rejects_a_destroyed_session() {
+        let mut session = Session::default();
+        session.set_status(SessionStatus::Destroyed);
+
+        let result = session.regenerate();
+        assert!(matches!(result, Err(SessionError::SessionDestroyedError)));
+    }
+
+    #[test]
+    fn store_section_round_trips_through_load_section() {
+        let mut session = Session::default();
+        assert!(session
+            .load_section::<User>()
+            .expect("expected load_section to succeed")
+            .is_none());
+
+        let user = User {
+            username: "fred".to_string(),
+            password: "hunter2".to_string(),
+        };
+        session
+            .store_section(&user)
+            .expect("expected store_section to succeed");
+        let username = session
+            .get::<String>("user.username")
+            .expect("expected get \"user.username\" to succeed");
+        assert_eq!(username, Some("fred".to_string()));
+        let loaded = session
+            .load_section::<User>()
+            .expect("expected load_section to succeed");
+        assert_eq!(loaded, Some(user));
+    }
+
+    #[test]
+    fn extend_inserts_entries_read_back_by_get_many() {
+        let mut session = Session::default();
+        session
+            .extend([("a", 1), ("b", 2)])
+            .expect("expected extend to succeed");
+        assert_eq!(session.status(), SessionStatus::Changed);
+        let values = session
+            .get_many::<u32>(&["b", "missing", "a"])
+            .expect("expected get_many to succeed");
+        assert_eq!(values, vec![Some(2), None, Some(1)]);
+    }
+
+    #[test]
+    fn dehydrate_round_trips_through_hydrate() {
+        let mut session = Session::default();
+        let user = User {
+            username: "fred".to_string(),
+            password: "hunter2".to_string(),
+        };
+        session
+            .dehydrate(&user)
+            .expect("expected dehydrate to succeed");
+        session
+            .insert_bytes("avatar", vec![1, 2, 3])
+            .expect("expected insert_bytes \"avatar\" to succeed");
+        let username = session
+            .get::<String>("username")
+            .expect("expected get \"username\" to succeed");
+        assert_eq!(username, Some("fred".to_string()));
+        let hydrated = session
+            .hydrate::<User>()
+            .expect("expected hydrate to succeed");
+        assert_eq!(hydrated, user);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_sections_use_the_configured_prefix() {
+        #[derive(Serialize, Deserialize, SessionSection)]
+        #[session(prefix = "prefs")]
+        struct Preferences {
+            theme: String,
+        }
+
+        #[derive(Serialize, Deserialize, SessionSection)]
+        struct ShoppingCart {
+            items: Vec<u64>,
+        }
+
+        assert_eq!(Preferences::PREFIX, "prefs");
+        assert_eq!(ShoppingCart::PREFIX, "shopping_cart");
+    }
+
+    #[test]
+    fn pop_front_returns_pushed_values_in_order() {
+        let mut session = Session::default();
+        for page in ["/a", "/b"] {
+            session
+                .push("recent", page.to_string())
+                .expect("expected push \"recent\" to succeed");
+        }
+
+        let first = session
+            .pop_front::<String>("recent")
+            .expect("expected pop_front \"recent\" to succeed");
+        assert_eq!(first, Some("/a".to_string()));
+        let second = session
+            .pop_front::<String>("recent")
+            .expect("expected pop_front \"recent\" to succeed");
+        assert_eq!(second, Some("/b".to_string()));
+        let empty = session
+            .pop_front::<String>("recent")
+            .expect("expected pop_front \"recent\" to succeed");
+        assert_eq!(empty, None);
+    }
 }
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Attributes of the client a session was issued to. Fields left as `None`
+/// are not checked.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionBinding {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BindingCheck {
+    /// The session has no binding to check against.
+    Unbound,
+    Matched,
+    Mismatched,
+}
+
+impl SessionBinding {
+    /// Compares this binding, as stored with the session, against the
+    /// fingerprint of the client presenting it.
+    pub fn check(&self, fingerprint: &SessionBinding) -> BindingCheck {
+        let matches = |stored: &Option<String>, presented: &Option<String>| {
+            stored.is_none() || stored == presented
+        };
+        if matches(&self.ip, &fingerprint.ip) && matches(&self.user_agent, &fingerprint.user_agent)
+        {
+            BindingCheck::Matched
+        } else {
+            BindingCheck::Mismatched
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_ignores_fields_that_were_not_bound() {
+        let binding = SessionBinding {
+            ip: None,
+            user_agent: Some("firefox".to_string()),
+        };
+        let fingerprint = SessionBinding {
+            ip: Some("10.0.0.1".to_string()),
+            user_agent: Some("firefox".to_string()),
+        };
+        assert_eq!(binding.check(&fingerprint), BindingCheck::Matched);
+    }
+
+    #[test]
+    fn check_detects_a_different_client() {
+        let binding = SessionBinding {
+            ip: Some("10.0.0.1".to_string()),
+            user_agent: Some("firefox".to_string()),
+        };
+        let fingerprint = SessionBinding {
+            ip: Some("10.0.0.2".to_string()),
+            user_agent: Some("firefox".to_string()),
+        };
+        assert_eq!(binding.check(&fingerprint), BindingCheck::Mismatched);
+    }
+}
@@ -0,0 +1,160 @@
+use std::marker::PhantomData;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Session, SessionError};
+
+/// A view into a single session entry, obtained from [`Session::entry`].
+///
+/// Values are deserialized when the entry is created and written back to the
+/// session whenever they change.
+pub enum Entry<'a, T> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+pub struct OccupiedEntry<'a, T> {
+    session: &'a mut Session,
+    key: String,
+    value: T,
+}
+
+pub struct VacantEntry<'a, T> {
+    session: &'a mut Session,
+    key: String,
+    value: PhantomData<T>,
+}
+
+impl<'a, T: Serialize + DeserializeOwned> Entry<'a, T> {
+    pub(crate) fn new(session: &'a mut Session, key: &str) -> Result<Self, SessionError> {
+        let key = key.to_string();
+        let entry = match session.get(&key)? {
+            Some(value) => Entry::Occupied(OccupiedEntry {
+                session,
+                key,
+                value,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                session,
+                key,
+                value: PhantomData,
+            }),
+        };
+        Ok(entry)
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: T) -> Result<T, SessionError> {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> Result<T, SessionError> {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_value()),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> Result<T, SessionError>
+    where
+        T: Default,
+    {
+        self.or_insert_with(T::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(self, f: F) -> Result<Self, SessionError> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(&mut entry.value);
+                entry.session.insert(&entry.key, &entry.value)?;
+                Ok(Entry::Occupied(entry))
+            }
+            Entry::Vacant(entry) => Ok(Entry::Vacant(entry)),
+        }
+    }
+}
+
+impl<'a, T: Serialize + DeserializeOwned> OccupiedEntry<'a, T> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Replaces the stored value, returning the previous one.
+    pub fn insert(&mut self, value: T) -> Result<T, SessionError> {
+        self.session.insert(&self.key, &value)?;
+        Ok(std::mem::replace(&mut self.value, value))
+    }
+
+    pub fn remove(self) -> Result<T, SessionError> {
+        self.session.remove::<T>(&self.key)?;
+        Ok(self.value)
+    }
+}
+
+impl<'a, T: Serialize + DeserializeOwned> VacantEntry<'a, T> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn insert(self, value: T) -> Result<T, SessionError> {
+        self.session.insert(&self.key, &value)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn or_insert_with_inserts_into_a_vacant_entry() {
+        let mut session = Session::default();
+        let value = session
+            .entry::<u32>("visits")
+            .expect("expected entry \"visits\" to succeed")
+            .or_insert_with(|| 1)
+            .expect("expected or_insert_with to succeed");
+        assert_eq!(value, 1);
+
+        let stored = session
+            .get::<u32>("visits")
+            .expect("expected get \"visits\" to succeed");
+        assert_eq!(stored, Some(1));
+    }
+
+    #[test]
+    fn and_modify_updates_an_occupied_entry() {
+        let mut session = Session::default();
+        session
+            .insert("visits", &1u32)
+            .expect("expected insert \"visits\" to succeed");
+
+        let value = session
+            .entry::<u32>("visits")
+            .expect("expected entry \"visits\" to succeed")
+            .and_modify(|visits| *visits += 1)
+            .expect("expected and_modify to succeed")
+            .or_insert(0)
+            .expect("expected or_insert to succeed");
+        assert_eq!(value, 2);
+
+        let stored = session
+            .get::<u32>("visits")
+            .expect("expected get \"visits\" to succeed");
+        assert_eq!(stored, Some(2));
+    }
+}
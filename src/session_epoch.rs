@@ -0,0 +1,306 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite, Table};
+
+use crate::{
+    session_storage::SessionStateTable, RefreshToken, RefreshTokenTable, SessionKey, SessionState,
+    SessionStorageError,
+};
+
+/// Holds the current invalidation epoch under the unit key.
+pub struct EpochTable {}
+
+impl Table for EpochTable {
+    type Key = ();
+    type OwnedKey = Self::Key;
+    type Value = u64;
+    type OwnedValue = Self::Value;
+}
+
+pub trait SessionEpochRead
+where
+    Self: Storage,
+{
+    fn current_epoch(&self) -> Result<u64, SessionStorageError<Self::Error>>;
+}
+
+impl<S> SessionEpochRead for S
+where
+    S: StorageRead<EpochTable>,
+{
+    fn current_epoch(&self) -> Result<u64, SessionStorageError<Self::Error>> {
+        let epoch = self.get(&())?;
+        Ok(epoch.map_or(0, |epoch| *epoch))
+    }
+}
+
+pub trait SessionEpochWrite
+where
+    Self: Storage,
+{
+    /// Advances the epoch, invalidating every session stored before it, and
+    /// returns the new epoch. Used as a kill switch after a security incident.
+    fn invalidate_all(&mut self) -> Result<u64, SessionStorageError<Self::Error>>;
+}
+
+impl<S> SessionEpochWrite for S
+where
+    S: StorageRead<EpochTable> + StorageWrite<EpochTable>,
+{
+    fn invalidate_all(&mut self) -> Result<u64, SessionStorageError<Self::Error>> {
+        let epoch = self.current_epoch()? + 1;
+        self.insert(&(), &epoch)?;
+        Ok(epoch)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EpochCheckError<StorageError> {
+    #[error("Session \"{0}\" was invalidated by a newer epoch")]
+    InvalidatedError(SessionKey),
+    #[error(transparent)]
+    StorageError(StorageError),
+}
+
+/// Wraps session storage and enforces the epoch in [`EpochTable`]. Sessions
+/// are stamped with the current epoch when first stored; sessions from an
+/// earlier epoch load as missing, and saving them fails with
+/// [`EpochCheckError::InvalidatedError`].
+/// [`crate::RefreshTokenTable`] is passed through, so refreshing through the
+/// wrapper also rejects invalidated sessions.
+pub struct EpochCheck<S> {
+    storage: S,
+}
+
+impl<S> EpochCheck<S> {
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S> EpochCheck<S>
+where
+    S: StorageRead<EpochTable>,
+{
+    fn current_epoch(&self) -> Result<u64, EpochCheckError<S::Error>> {
+        let epoch = StorageRead::<EpochTable>::get(&self.storage, &())
+            .map_err(EpochCheckError::StorageError)?;
+        Ok(epoch.map_or(0, |epoch| *epoch))
+    }
+}
+
+impl<S: Storage> Storage for EpochCheck<S> {
+    type Error = EpochCheckError<S::Error>;
+}
+
+impl<S> StorageRead<SessionStateTable> for EpochCheck<S>
+where
+    S: StorageRead<SessionStateTable> + StorageRead<EpochTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        let state = StorageRead::<SessionStateTable>::get(&self.storage, key)
+            .map_err(EpochCheckError::StorageError)?;
+        let Some(state) = state else {
+            return Ok(None);
+        };
+        let current = self.current_epoch()?;
+        // Sessions stored before epochs were enabled belong to the first one.
+        let valid = state.metadata().epoch().unwrap_or(0) >= current;
+        Ok(valid.then_some(state))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        let state = StorageRead::<SessionStateTable>::get(self, key)?;
+        Ok(state.is_some())
+    }
+}
+
+impl<S> StorageWrite<SessionStateTable> for EpochCheck<S>
+where
+    S: StorageWrite<SessionStateTable> + StorageRead<SessionStateTable> + StorageRead<EpochTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let current = self.current_epoch()?;
+        let stamped = value.metadata().epoch();
+        let epoch = match stamped {
+            Some(epoch) => epoch,
+            None => {
+                // A session that was never loaded has not seen its stamp, so
+                // take it from the stored copy rather than stamping it into
+                // the new epoch.
+                let stored = StorageRead::<SessionStateTable>::get(&self.storage, key)
+                    .map_err(EpochCheckError::StorageError)?;
+                match stored {
+                    Some(stored) => stored.metadata().epoch().unwrap_or(0),
+                    None => current,
+                }
+            }
+        };
+        if epoch < current {
+            return Err(EpochCheckError::InvalidatedError(key.clone()));
+        }
+        let result = if stamped.is_some() {
+            self.storage.insert(key, value)
+        } else {
+            let mut value = value.clone();
+            value.metadata_mut().set_epoch(epoch);
+            self.storage.insert(key, &value)
+        };
+        result.map_err(EpochCheckError::StorageError)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.storage
+            .remove(key)
+            .map_err(EpochCheckError::StorageError)
+    }
+}
+
+impl<S> StorageTemp<SessionStateTable> for EpochCheck<S>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage.ttl(key).map_err(EpochCheckError::StorageError)
+    }
+}
+
+impl<S> StorageRead<RefreshTokenTable> for EpochCheck<S>
+where
+    S: StorageRead<RefreshTokenTable>,
+{
+    fn get(&self, key: &String) -> Result<Option<Cow<'_, RefreshToken>>, Self::Error> {
+        self.storage.get(key).map_err(EpochCheckError::StorageError)
+    }
+
+    fn exists(&self, key: &String) -> Result<bool, Self::Error> {
+        self.storage
+            .exists(key)
+            .map_err(EpochCheckError::StorageError)
+    }
+}
+
+impl<S> StorageWrite<RefreshTokenTable> for EpochCheck<S>
+where
+    S: StorageWrite<RefreshTokenTable>,
+{
+    fn insert(
+        &mut self,
+        key: &String,
+        value: &RefreshToken,
+    ) -> Result<Option<RefreshToken>, Self::Error> {
+        self.storage
+            .insert(key, value)
+            .map_err(EpochCheckError::StorageError)
+    }
+
+    fn remove(&mut self, key: &String) -> Result<Option<RefreshToken>, Self::Error> {
+        self.storage
+            .remove(key)
+            .map_err(EpochCheckError::StorageError)
+    }
+}
+
+impl<S> StorageTemp<RefreshTokenTable> for EpochCheck<S>
+where
+    S: StorageTemp<RefreshTokenTable>,
+{
+    fn ttl(&self, key: &String) -> Result<Duration, Self::Error> {
+        self.storage.ttl(key).map_err(EpochCheckError::StorageError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn invalidate_all_rejects_sessions_from_earlier_epochs() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        EpochCheck::new(&mut storage)
+            .session_save(&session)
+            .expect("expected save to succeed");
+        let loaded = EpochCheck::new(&mut storage)
+            .session_load(session.id())
+            .expect("expected load to succeed")
+            .expect("expected session to be present");
+        assert_eq!(loaded.state().metadata().epoch(), Some(0));
+
+        let epoch = storage
+            .invalidate_all()
+            .expect("expected invalidate_all to succeed");
+        assert_eq!(epoch, 1);
+        let mut storage = EpochCheck::new(&mut storage);
+        let reloaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert!(reloaded.is_none());
+
+        storage
+            .session_destroy(session.id())
+            .expect("expected destroy to succeed");
+        let result = storage.session_save(&loaded);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                EpochCheckError::InvalidatedError(key)
+            )) if &key == session.id()
+        ));
+        let exists = storage
+            .session_exists(session.id())
+            .expect("expected exists to succeed");
+        assert!(!exists);
+    }
+
+    #[test]
+    fn unstamped_sessions_do_not_survive_invalidation() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        EpochCheck::new(&mut storage)
+            .session_save(&session)
+            .expect("expected save to succeed");
+        storage
+            .invalidate_all()
+            .expect("expected invalidate_all to succeed");
+
+        let mut storage = EpochCheck::new(&mut storage);
+        let result = storage.session_save(&session);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::StorageError(
+                EpochCheckError::InvalidatedError(_)
+            ))
+        ));
+        let loaded = storage
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert!(loaded.is_none());
+    }
+
+    #[test]
+    fn sessions_without_an_epoch_belong_to_the_first_one() {
+        let mut storage = MockStorage::new();
+        let session = Session::default();
+        storage
+            .session_save(&session)
+            .expect("expected save to succeed");
+        storage
+            .invalidate_all()
+            .expect("expected invalidate_all to succeed");
+
+        let loaded = EpochCheck::new(&mut storage)
+            .session_load(session.id())
+            .expect("expected load to succeed");
+        assert!(loaded.is_none());
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use crate::Session;
+
+/// Receives lifecycle events from [`crate::SessionModel`], e.g. to audit logins
+/// and logouts. Every method defaults to doing nothing. Handlers are shared
+/// between threads along with the models they are registered on.
+pub trait SessionEventHandler: Send + Sync {
+    /// Called after a session is stored for the first time.
+    fn on_create(&self, _session: &Session) {}
+
+    /// Called after every successful save, including the first.
+    fn on_save(&self, _session: &Session) {}
+
+    /// Called after a stored session is destroyed.
+    fn on_destroy(&self, _session: &Session) {}
+
+    /// Called instead of [`SessionEventHandler::on_destroy`] when a session is
+    /// destroyed because it outlived its [`crate::ExpirationPolicy`].
+    fn on_expire(&self, _session: &Session) {}
+}
+
+/// The handlers notified of a model's lifecycle events. Cloning is cheap, so
+/// one registry can be built at startup and handed to every model.
+#[derive(Clone, Default)]
+pub struct SessionEvents {
+    handlers: Vec<Arc<dyn SessionEventHandler>>,
+}
+
+impl SessionEvents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, handler: impl SessionEventHandler + 'static) {
+        self.handlers.push(Arc::new(handler));
+    }
+
+    pub(crate) fn create(&self, session: &Session) {
+        self.handlers.iter().for_each(|h| h.on_create(session));
+    }
+
+    pub(crate) fn save(&self, session: &Session) {
+        self.handlers.iter().for_each(|h| h.on_save(session));
+    }
+
+    pub(crate) fn destroy(&self, session: &Session) {
+        self.handlers.iter().for_each(|h| h.on_destroy(session));
+    }
+
+    pub(crate) fn expire(&self, session: &Session) {
+        self.handlers.iter().for_each(|h| h.on_expire(session));
+    }
+}
@@ -0,0 +1,18 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A session entry with a compile-time key and value type.
+///
+/// ```
+/// use lushus_session::SessionField;
+///
+/// struct UserId;
+///
+/// impl SessionField for UserId {
+///     const KEY: &'static str = "user_id";
+///     type Value = u64;
+/// }
+/// ```
+pub trait SessionField {
+    const KEY: &'static str;
+    type Value: Serialize + DeserializeOwned;
+}
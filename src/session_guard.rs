@@ -0,0 +1,69 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{
+    instrument, SessionModel, SessionStorageError, SessionStorageRead, SessionStorageWrite,
+};
+
+/// Saves the wrapped [`SessionModel`] when dropped, so a handler cannot forget
+/// to persist its changes. Errors raised while saving on drop are only logged;
+/// call [`SessionGuard::commit`] to observe them. Nothing is saved when the
+/// guard is dropped while unwinding from a panic, so a half-applied handler's
+/// changes are not persisted.
+pub struct SessionGuard<'a, S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    model: &'a mut SessionModel<S>,
+    committed: bool,
+}
+
+impl<'a, S> SessionGuard<'a, S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    pub fn new(model: &'a mut SessionModel<S>) -> Self {
+        Self {
+            model,
+            committed: false,
+        }
+    }
+
+    pub fn commit(mut self) -> Result<(), SessionStorageError<S::Error>> {
+        self.committed = true;
+        self.model.save()
+    }
+}
+
+impl<S> Deref for SessionGuard<'_, S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    type Target = SessionModel<S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.model
+    }
+}
+
+impl<S> DerefMut for SessionGuard<'_, S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.model
+    }
+}
+
+impl<S> Drop for SessionGuard<'_, S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    fn drop(&mut self) {
+        if self.committed || std::thread::panicking() {
+            return;
+        }
+        if self.model.save().is_err() {
+            instrument::warn(self.model.id(), "failed to save session on drop");
+        }
+    }
+}
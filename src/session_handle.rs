@@ -0,0 +1,127 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    SessionError, SessionKey, SessionModel, SessionStorageError, SessionStorageRead,
+    SessionStorageWrite,
+};
+
+/// A cheap-to-clone handle to a [`SessionModel`], so every part of a request
+/// that needs the session can read and change it without threading a
+/// `&mut` through. Changes made through any clone are saved together by a
+/// single call to [`SessionHandle::save`].
+///
+/// If a thread panics while changing the session, every later use of the
+/// handle panics too, so a half-applied change is never saved.
+pub struct SessionHandle<S> {
+    model: Arc<Mutex<SessionModel<S>>>,
+}
+
+impl<S> Clone for SessionHandle<S> {
+    fn clone(&self) -> Self {
+        let model = Arc::clone(&self.model);
+        Self { model }
+    }
+}
+
+impl<S> SessionHandle<S> {
+    pub fn new(model: SessionModel<S>) -> Self {
+        let model = Arc::new(Mutex::new(model));
+        Self { model }
+    }
+
+    pub fn id(&self) -> SessionKey {
+        self.lock().id().clone()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
+        self.lock().get(key)
+    }
+
+    pub fn insert<T: Serialize + DeserializeOwned>(
+        &self,
+        key: &str,
+        value: T,
+    ) -> Result<Option<T>, SessionError> {
+        self.lock().insert(key, value)
+    }
+
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
+        self.lock().remove(key)
+    }
+
+    /// Runs `f` with exclusive access to the model. Other clones block until
+    /// it returns.
+    pub fn with<R>(&self, f: impl FnOnce(&mut SessionModel<S>) -> R) -> R {
+        f(&mut self.lock())
+    }
+
+    /// Returns the model if this is the last handle to it.
+    pub fn into_model(self) -> Option<SessionModel<S>> {
+        let model = Arc::into_inner(self.model)?;
+        Some(
+            model
+                .into_inner()
+                .expect("session handle poisoned by a panic"),
+        )
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SessionModel<S>> {
+        self.model
+            .lock()
+            .expect("session handle poisoned by a panic")
+    }
+}
+
+impl<S> SessionHandle<S>
+where
+    S: SessionStorageRead + SessionStorageWrite,
+{
+    /// Saves the changes made through every clone of this handle.
+    pub fn save(&self) -> Result<(), SessionStorageError<S::Error>> {
+        self.lock().save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_util::MockStorage;
+
+    #[test]
+    fn session_handle_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SessionHandle<MockStorage>>();
+    }
+
+    #[test]
+    fn session_handle_clones_share_one_session() {
+        let mut storage = MockStorage::new();
+        let model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        let handle = SessionHandle::new(model);
+        let other = handle.clone();
+        handle
+            .insert("name", "fred".to_string())
+            .expect("expected insert to succeed");
+        other
+            .insert("theme", "dark".to_string())
+            .expect("expected insert to succeed");
+        assert_eq!(
+            handle
+                .get::<String>("theme")
+                .expect("expected get to succeed"),
+            Some("dark".to_string())
+        );
+        other.save().expect("expected save to succeed");
+
+        assert!(handle.clone().into_model().is_none());
+        drop(other);
+        let key = handle.id();
+        assert!(handle.into_model().is_some());
+        let state = storage.map.get(&key).expect("expected session to be saved");
+        assert_eq!(state.len(), 2);
+    }
+}
@@ -1,24 +1,176 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    borrow::Borrow,
+    fmt::{Debug, Display, Formatter},
+    str::FromStr,
+};
 
 use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use subtle::ConstantTimeEq;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Eq, PartialEq, thiserror::Error)]
+pub enum SessionKeyError {
+    #[error("Session key length {0} is outside the allowed range")]
+    InvalidLengthError(usize),
+    #[error("Session key contains invalid character {0:?}")]
+    InvalidCharacterError(char),
+}
+
+/// Only a short prefix of the key is printed by `Debug` and `Display`, so keys
+/// can be logged without leaking them; use [`AsRef<str>`] for the full key.
+#[derive(Clone, Eq, PartialEq, Hash, serde::Serialize)]
 pub struct SessionKey(String);
 
 impl Display for SessionKey {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.redacted())
     }
 }
 
+impl Debug for SessionKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SessionKey").field(&self.redacted()).finish()
+    }
+}
+
+/// Overwrites the key's memory when it is dropped.
+#[cfg(feature = "zeroize")]
+impl Drop for SessionKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.0);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for SessionKey {}
+
 impl SessionKey {
+    pub const MIN_LENGTH: usize = 16;
+    pub const MAX_LENGTH: usize = 128;
+
     pub fn generate() -> Self {
-        let value = std::iter::repeat(())
-            .map(|()| OsRng.sample(Alphanumeric))
-            .take(64)
-            .collect::<Vec<_>>();
-        let key = String::from_utf8(value).unwrap();
-        Self(key)
+        Self(random_string(64))
+    }
+
+    /// Compares keys in time independent of their contents, for checking a
+    /// presented key against a known one. Only the lengths can leak.
+    pub fn ct_eq(&self, other: &SessionKey) -> bool {
+        ct_eq(&self.0, &other.0)
+    }
+
+    pub fn as_key_ref(&self) -> SessionKeyRef<'_> {
+        SessionKeyRef(&self.0)
+    }
+
+    /// A short prefix of the key that is safe to write to logs.
+    fn redacted(&self) -> String {
+        redacted(&self.0)
+    }
+
+    /// The hex-encoded HMAC-SHA256 of the key under `secret`, used in place of
+    /// the key by [`crate::HashedKeys`].
+    #[cfg(feature = "hashed-keys")]
+    pub(crate) fn hmac(&self, secret: &[u8]) -> Self {
+        use hmac::{Hmac, Mac};
+
+        let mut mac =
+            Hmac::<sha2::Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(self.0.as_bytes());
+        Self(hex(&mac.finalize().into_bytes()))
+    }
+
+    /// Generates a ULID-formatted key. The leading characters encode the
+    /// creation time in milliseconds, so keys sort by age. ULIDs carry 80 random
+    /// bits, fewer than [`SessionKey::generate`].
+    #[cfg(feature = "ulid")]
+    pub fn ulid() -> Self {
+        Self(ulid::Ulid::new().to_string())
+    }
+
+    /// The creation time encoded in a key generated by [`SessionKey::ulid`],
+    /// to the millisecond. `None` for keys in any other format.
+    #[cfg(feature = "ulid")]
+    pub fn created_at(&self) -> Option<std::time::SystemTime> {
+        let ulid = ulid::Ulid::from_string(&self.0).ok()?;
+        Some(ulid.datetime())
+    }
+
+    /// Generates a key formatted as a random (version 4) UUID.
+    #[cfg(feature = "uuid")]
+    pub fn uuid_v4() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+fn redacted(key: &str) -> String {
+    let prefix = key.chars().take(6).collect::<String>();
+    format!("{prefix}…")
+}
+
+/// Checks an untrusted value against the rules for keys. Accepts ASCII
+/// alphanumerics and `-`, which covers every format this crate generates.
+fn validate(value: &str) -> Result<(), SessionKeyError> {
+    if !(SessionKey::MIN_LENGTH..=SessionKey::MAX_LENGTH).contains(&value.len()) {
+        return Err(SessionKeyError::InvalidLengthError(value.len()));
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-')
+    {
+        return Err(SessionKeyError::InvalidCharacterError(c));
+    }
+    Ok(())
+}
+
+/// Compares secrets in time independent of their contents.
+pub(crate) fn ct_eq(a: &str, b: &str) -> bool {
+    a.as_bytes().ct_eq(b.as_bytes()).into()
+}
+
+/// The hex-encoded SHA-256 of `value`.
+pub(crate) fn sha256_hex(value: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    hex(&Sha256::digest(value))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Generates `len` random alphanumeric characters from the OS random source.
+pub(crate) fn random_string(len: usize) -> String {
+    let value = std::iter::repeat(())
+        .map(|()| OsRng.sample(Alphanumeric))
+        .take(len)
+        .collect::<Vec<_>>();
+    String::from_utf8(value).unwrap()
+}
+
+impl FromStr for SessionKey {
+    type Err = SessionKeyError;
+
+    /// Parses an untrusted value, such as a cookie, into a key.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        validate(value)?;
+        Ok(Self(value.to_string()))
+    }
+}
+
+/// Keys read back from storage, e.g. from the user index, are checked like
+/// parsed keys.
+impl<'de> serde::Deserialize<'de> for SessionKey {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        validate(&value).map_err(serde::de::Error::custom)?;
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<&str> for SessionKey {
+    type Error = SessionKeyError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
     }
 }
 
@@ -33,3 +185,151 @@ impl Default for SessionKey {
         Self::generate()
     }
 }
+
+/// Lets maps keyed by [`SessionKey`] be queried with a `&str`.
+impl Borrow<str> for SessionKey {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A validated key borrowed from elsewhere, such as a request header, so it
+/// can be looked up without allocating a [`SessionKey`]. Printed redacted
+/// like [`SessionKey`].
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SessionKeyRef<'a>(&'a str);
+
+impl<'a> SessionKeyRef<'a> {
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+
+    pub fn to_key(&self) -> SessionKey {
+        SessionKey(self.0.to_string())
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SessionKeyRef<'a> {
+    type Error = SessionKeyError;
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        validate(value)?;
+        Ok(Self(value))
+    }
+}
+
+impl Display for SessionKeyRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", redacted(self.0))
+    }
+}
+
+impl Debug for SessionKeyRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SessionKeyRef")
+            .field(&redacted(self.0))
+            .finish()
+    }
+}
+
+impl PartialEq<SessionKeyRef<'_>> for SessionKey {
+    fn eq(&self, other: &SessionKeyRef<'_>) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str_accepts_generated_keys() {
+        let key = SessionKey::generate();
+        let parsed = key
+            .as_ref()
+            .parse::<SessionKey>()
+            .expect("expected generated key to parse");
+        assert_eq!(parsed, key);
+    }
+
+    #[test]
+    fn from_str_rejects_keys_outside_the_allowed_length() {
+        let result = SessionKey::try_from("abc");
+        assert_eq!(result, Err(SessionKeyError::InvalidLengthError(3)));
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_characters() {
+        let result = SessionKey::try_from("abcdefghijklmnop;");
+        assert_eq!(result, Err(SessionKeyError::InvalidCharacterError(';')));
+    }
+
+    #[test]
+    fn deserialize_validates_the_key() {
+        let key = SessionKey::generate();
+        let json = serde_json::to_string(&key).expect("expected serialize to succeed");
+        let parsed =
+            serde_json::from_str::<SessionKey>(&json).expect("expected deserialize to succeed");
+        assert_eq!(parsed, key);
+
+        let result = serde_json::from_str::<SessionKey>("\"abcdefghijklmnop;\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn key_refs_look_up_keys_without_allocating() {
+        let key = SessionKey::generate();
+        let mut map = std::collections::HashMap::new();
+        map.insert(key.clone(), 1);
+
+        let header = key.as_ref().to_string();
+        let key_ref = SessionKeyRef::try_from(header.as_str()).expect("expected key to parse");
+        assert_eq!(key, key_ref);
+        assert_eq!(map.get(key_ref.as_str()), Some(&1));
+        assert_eq!(key_ref.to_key(), key);
+        assert_eq!(
+            SessionKeyRef::try_from("abc"),
+            Err(SessionKeyError::InvalidLengthError(3))
+        );
+    }
+
+    #[test]
+    fn ct_eq_compares_keys() {
+        let key = SessionKey::generate();
+        assert!(key.ct_eq(&key.clone()));
+        assert!(!key.ct_eq(&SessionKey::generate()));
+    }
+
+    #[test]
+    fn debug_and_display_redact_the_key() {
+        let key = SessionKey::try_from("abcdefghijklmnop").expect("expected key to parse");
+        assert_eq!(key.to_string(), "abcdef…");
+        assert_eq!(format!("{key:?}"), "SessionKey(\"abcdef…\")");
+    }
+
+    #[cfg(feature = "ulid")]
+    #[test]
+    fn ulid_generates_a_parseable_ulid() {
+        let key = SessionKey::ulid();
+        ulid::Ulid::from_string(key.as_ref()).expect("expected key to be a ULID");
+    }
+
+    #[cfg(feature = "ulid")]
+    #[test]
+    fn created_at_reads_the_time_from_ulid_keys() {
+        let before = std::time::SystemTime::now() - std::time::Duration::from_millis(1);
+        let created_at = SessionKey::ulid()
+            .created_at()
+            .expect("expected a creation time");
+        assert!(created_at >= before);
+        assert_eq!(SessionKey::generate().created_at(), None);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_v4_generates_a_version_4_uuid() {
+        let key = SessionKey::uuid_v4();
+        let uuid = uuid::Uuid::parse_str(key.as_ref()).expect("expected key to be a UUID");
+        assert_eq!(uuid.get_version_num(), 4);
+    }
+}
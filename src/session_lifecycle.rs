@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{SessionError, SessionStorageError};
+
+/// How far the holder of a session has authenticated, changed through
+/// [`crate::SessionModel::authenticate`], [`crate::SessionModel::elevate`] and
+/// [`crate::SessionModel::downgrade`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionLifecycle {
+    #[default]
+    Anonymous,
+    Authenticated,
+    /// Recently re-authenticated, allowed to perform sensitive actions.
+    Elevated,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionLifecycleError<StorageError> {
+    #[error("Session cannot move from {0:?} to {1:?}")]
+    InvalidTransitionError(SessionLifecycle, SessionLifecycle),
+    #[error(transparent)]
+    SessionError(#[from] SessionError),
+    #[error(transparent)]
+    StorageError(#[from] SessionStorageError<StorageError>),
+}
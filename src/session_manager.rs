@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use crate::{
+    SessionKey, SessionModel, SessionStatus, SessionStorageError, SessionStorageRead,
+    SessionStorageWrite,
+};
+
+/// What the HTTP layer should do with the session cookie once a request has
+/// been handled.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SessionCookie {
+    /// Send the cookie with this key, expiring after `max_age`.
+    Set { key: SessionKey, max_age: Duration },
+    /// Tell the client to drop its cookie.
+    Remove,
+    /// Leave the cookie as it is.
+    Unchanged,
+}
+
+/// Framework-agnostic request lifecycle for sessions.
+///
+/// Middleware calls [`SessionManager::load_or_create`] with the incoming cookie
+/// value before the handler runs, and [`SessionManager::finish`] afterwards to
+/// persist the session and learn what to do with the cookie.
+pub struct SessionManager<S> {
+    storage: S,
+    timeout: Duration,
+}
+
+impl<S> SessionManager<S>
+where
+    S: SessionStorageRead + SessionStorageWrite + Clone,
+{
+    pub fn new(storage: S, timeout: Duration) -> Self {
+        Self { storage, timeout }
+    }
+
+    /// Loads the session named by `cookie`, or starts a new one if the cookie
+    /// is missing, malformed or refers to a session that no longer exists.
+    /// Sessions started in place of a logged-out one report
+    /// [`SessionModel::was_logged_out`], so the user can be told why.
+    pub fn load_or_create(
+        &self,
+        cookie: Option<&str>,
+    ) -> Result<SessionModel<S>, SessionStorageError<S::Error>> {
+        if let Some(key) = cookie.and_then(|cookie| cookie.parse::<SessionKey>().ok()) {
+            match SessionModel::load(self.storage.clone(), &key) {
+                Ok(Some(model)) => return Ok(model),
+                Ok(None) => {}
+                Err(SessionStorageError::LoggedOutError(_)) => {
+                    let mut model = SessionModel::new(self.storage.clone(), self.timeout);
+                    model.set_logged_out();
+                    return Ok(model);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(SessionModel::new(self.storage.clone(), self.timeout))
+    }
+
+    /// Saves the session if it changed and decides how the cookie should be
+    /// updated.
+    pub fn finish(
+        &self,
+        cookie: Option<&str>,
+        mut model: SessionModel<S>,
+    ) -> Result<SessionCookie, SessionStorageError<S::Error>> {
+        let sent = cookie.is_some_and(|cookie| cookie == model.id().as_ref());
+        let action = match model.session().status() {
+            SessionStatus::Destroyed if cookie.is_some() => SessionCookie::Remove,
+            SessionStatus::Destroyed => SessionCookie::Unchanged,
+            SessionStatus::Changed => {
+                model.save()?;
+                SessionCookie::Set {
+                    key: model.id().clone(),
+                    max_age: model.timeout(),
+                }
+            }
+            SessionStatus::Unchanged if model.is_persisted() && !sent => SessionCookie::Set {
+                key: model.id().clone(),
+                max_age: model.timeout(),
+            },
+            SessionStatus::Unchanged if !model.is_persisted() && cookie.is_some() => {
+                SessionCookie::Remove
+            }
+            SessionStatus::Unchanged => SessionCookie::Unchanged,
+        };
+        Ok(action)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+    use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+    use super::{SessionCookie, SessionManager};
+    use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+    #[derive(Clone)]
+    struct TestStorage {
+        map: Rc<RefCell<HashMap<SessionKey, SessionState>>>,
+    }
+
+    impl TestStorage {
+        fn new() -> Self {
+            let map = Rc::new(RefCell::new(HashMap::new()));
+            TestStorage { map }
+        }
+    }
+
+    impl Storage for TestStorage {
+        type Error = std::convert::Infallible;
+    }
+
+    impl StorageRead<SessionStateTable> for TestStorage {
+        fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+            let value = self.map.borrow().get(key).cloned();
+            Ok(value.map(Cow::Owned))
+        }
+
+        fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+            Ok(self.map.borrow().contains_key(key))
+        }
+    }
+
+    impl StorageWrite<SessionStateTable> for TestStorage {
+        fn insert(
+            &mut self,
+            key: &SessionKey,
+            value: &SessionState,
+        ) -> Result<Option<SessionState>, Self::Error> {
+            Ok(self.map.borrow_mut().insert(key.clone(), value.clone()))
+        }
+
+        fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+            Ok(self.map.borrow_mut().remove(key))
+        }
+    }
+
+    impl StorageTemp<SessionStateTable> for TestStorage {
+        fn ttl(&self, _key: &SessionKey) -> Result<Duration, Self::Error> {
+            Ok(Duration::from_secs(100))
+        }
+    }
+
+    #[test]
+    fn finish_sets_the_cookie_for_a_changed_session() {
+        let manager = SessionManager::new(TestStorage::new(), Duration::from_secs(100));
+        let mut model = manager
+            .load_or_create(None)
+            .expect("Failed to create session model");
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        let id = model.id().clone();
+
+        let cookie = manager
+            .finish(None, model)
+            .expect("Failed to finish session");
+        assert_eq!(
+            cookie,
+            SessionCookie::Set {
+                key: id.clone(),
+                max_age: Duration::from_secs(100)
+            }
+        );
+
+        let model = manager
+            .load_or_create(Some(id.as_ref()))
+            .expect("Failed to load session model");
+        assert_eq!(model.id(), &id);
+        let cookie = manager
+            .finish(Some(id.as_ref()), model)
+            .expect("Failed to finish session");
+        assert_eq!(cookie, SessionCookie::Unchanged);
+    }
+
+    #[test]
+    fn finish_removes_a_cookie_for_an_unknown_session() {
+        let manager = SessionManager::new(TestStorage::new(), Duration::from_secs(100));
+        let cookie = SessionKey::generate();
+        let model = manager
+            .load_or_create(Some(cookie.as_ref()))
+            .expect("Failed to create session model");
+        assert_ne!(model.id(), &cookie);
+
+        let action = manager
+            .finish(Some(cookie.as_ref()), model)
+            .expect("Failed to finish session");
+        assert_eq!(action, SessionCookie::Remove);
+    }
+
+    #[test]
+    fn load_or_create_replaces_a_logged_out_session() {
+        let manager = SessionManager::new(TestStorage::new(), Duration::from_secs(100));
+        let mut model = manager
+            .load_or_create(None)
+            .expect("Failed to create session model");
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        let id = model.id().clone();
+        manager
+            .finish(None, model)
+            .expect("Failed to finish session");
+
+        let mut model = manager
+            .load_or_create(Some(id.as_ref()))
+            .expect("Failed to load session model");
+        assert!(!model.was_logged_out());
+        model
+            .destroy_soft(Duration::from_secs(60))
+            .expect("Failed to soft-destroy session");
+
+        let model = manager
+            .load_or_create(Some(id.as_ref()))
+            .expect("Failed to create session model");
+        assert!(model.was_logged_out());
+        assert_ne!(model.id(), &id);
+    }
+}
@@ -0,0 +1,220 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SessionBinding, SessionKey, SessionLifecycle};
+
+/// Bookkeeping stored alongside the session values.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    created_at: SystemTime,
+    #[serde(default)]
+    timeout: Option<Duration>,
+    #[serde(default)]
+    revision: u64,
+    #[serde(default)]
+    user_id: Option<String>,
+    #[serde(default)]
+    binding: Option<SessionBinding>,
+    #[serde(default)]
+    last_accessed: Option<SystemTime>,
+    #[serde(default)]
+    access_count: u64,
+    #[serde(default)]
+    epoch: Option<u64>,
+    #[serde(default)]
+    lifecycle: SessionLifecycle,
+    #[serde(default)]
+    elevated_until: Option<SystemTime>,
+    #[serde(default)]
+    tombstone: bool,
+    #[serde(default)]
+    base_timeout: Option<Duration>,
+    #[serde(skip)]
+    ttl: Option<Duration>,
+    #[serde(skip)]
+    replaces: Option<SessionKey>,
+    #[serde(skip)]
+    legacy: bool,
+}
+
+impl SessionMetadata {
+    /// Stands in for the metadata of a session stored without any, whose
+    /// creation time is unknown.
+    pub(crate) fn legacy() -> Self {
+        Self {
+            legacy: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether this metadata was recreated by [`SessionMetadata::legacy`]
+    /// and has not been stored yet.
+    pub(crate) fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub(crate) fn clear_legacy(&mut self) {
+        self.legacy = false;
+    }
+
+    pub fn created_at(&self) -> SystemTime {
+        self.created_at
+    }
+
+    /// The TTL requested for this session, overriding the storage default.
+    /// Storage should apply [`SessionMetadata::ttl`] instead, which accounts
+    /// for jitter.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+
+    /// The TTL storage should apply to the write at hand: the
+    /// [`SessionMetadata::timeout`], shortened for that write only when
+    /// [`crate::SessionModel::set_timeout_jitter`] is enabled. The shortened
+    /// TTL is never stored.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl.or(self.timeout)
+    }
+
+    pub(crate) fn set_ttl(&mut self, ttl: Option<Duration>) {
+        self.ttl = ttl;
+    }
+
+    /// Incremented on every save, used to detect concurrent modification.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub(crate) fn set_revision(&mut self, revision: u64) {
+        self.revision = revision;
+    }
+
+    /// The user the session belongs to, used by [`crate::UserIndex`].
+    pub fn user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    pub(crate) fn set_user_id(&mut self, user_id: Option<String>) {
+        self.user_id = user_id;
+    }
+
+    pub fn binding(&self) -> Option<&SessionBinding> {
+        self.binding.as_ref()
+    }
+
+    pub(crate) fn set_binding(&mut self, binding: SessionBinding) {
+        self.binding = Some(binding);
+    }
+
+    /// When the session was last loaded, `None` if it never has been.
+    pub fn last_accessed(&self) -> Option<SystemTime> {
+        self.last_accessed
+    }
+
+    /// How many times the session has been loaded.
+    pub fn access_count(&self) -> u64 {
+        self.access_count
+    }
+
+    /// The invalidation epoch the session was first stored in, see
+    /// [`crate::EpochCheck`].
+    pub fn epoch(&self) -> Option<u64> {
+        self.epoch
+    }
+
+    pub(crate) fn set_epoch(&mut self, epoch: u64) {
+        self.epoch = Some(epoch);
+    }
+
+    pub fn lifecycle(&self) -> SessionLifecycle {
+        self.lifecycle
+    }
+
+    pub(crate) fn set_lifecycle(&mut self, lifecycle: SessionLifecycle) {
+        self.lifecycle = lifecycle;
+    }
+
+    /// When a [`SessionLifecycle::Elevated`] session stops being elevated.
+    pub fn elevated_until(&self) -> Option<SystemTime> {
+        self.elevated_until
+    }
+
+    pub(crate) fn set_elevated_until(&mut self, elevated_until: Option<SystemTime>) {
+        self.elevated_until = elevated_until;
+    }
+
+    /// Whether this is the placeholder left by
+    /// [`crate::SessionModel::destroy_soft`].
+    pub fn is_tombstone(&self) -> bool {
+        self.tombstone
+    }
+
+    pub(crate) fn set_tombstone(&mut self) {
+        self.tombstone = true;
+    }
+
+    /// The TTL to go back to once the session leaves a lifecycle with a TTL
+    /// of its own, see [`crate::SessionModel::set_lifecycle_timeout`].
+    pub(crate) fn base_timeout(&self) -> Option<Duration> {
+        self.base_timeout
+    }
+
+    pub(crate) fn set_base_timeout(&mut self, timeout: Option<Duration>) {
+        self.base_timeout = timeout;
+    }
+
+    /// Forgets who the session belongs to, dropping it back to an anonymous
+    /// session with its base TTL.
+    pub(crate) fn reset_identity(&mut self) {
+        self.user_id = None;
+        self.binding = None;
+        self.lifecycle = SessionLifecycle::Anonymous;
+        self.elevated_until = None;
+        if let Some(timeout) = self.base_timeout.take() {
+            self.timeout = Some(timeout);
+        }
+    }
+
+    /// The key the session was stored under before it was regenerated, until
+    /// it is stored under the new one. Never stored, but lets storage
+    /// wrappers tell a rotation from a new session.
+    pub(crate) fn replaces(&self) -> Option<&SessionKey> {
+        self.replaces.as_ref()
+    }
+
+    pub(crate) fn set_replaces(&mut self, key: Option<SessionKey>) {
+        self.replaces = key;
+    }
+
+    pub(crate) fn record_access(&mut self) {
+        self.last_accessed = Some(SystemTime::now());
+        self.access_count += 1;
+    }
+}
+
+impl Default for SessionMetadata {
+    fn default() -> Self {
+        Self {
+            created_at: SystemTime::now(),
+            timeout: None,
+            revision: 0,
+            user_id: None,
+            binding: None,
+            last_accessed: None,
+            access_count: 0,
+            epoch: None,
+            lifecycle: SessionLifecycle::Anonymous,
+            elevated_until: None,
+            tombstone: false,
+            base_timeout: None,
+            ttl: None,
+            replaces: None,
+            legacy: false,
+        }
+    }
+}
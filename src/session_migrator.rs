@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use crate::{SessionError, SessionState};
+
+type Migration = Box<dyn Fn(&mut SessionState) -> Result<(), SessionError> + Send + Sync>;
+
+/// A registry of upgrade functions that bring stored session state up to the
+/// schema version the application currently expects.
+#[derive(Default)]
+pub struct SessionMigrator {
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl SessionMigrator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the migration that upgrades state from version `from` to
+    /// `from + 1`.
+    pub fn register<F>(mut self, from: u32, migration: F) -> Self
+    where
+        F: Fn(&mut SessionState) -> Result<(), SessionError> + Send + Sync + 'static,
+    {
+        self.migrations.insert(from, Box::new(migration));
+        self
+    }
+
+    /// The current schema version, one past the newest registered migration.
+    pub fn version(&self) -> u32 {
+        self.migrations
+            .keys()
+            .next_back()
+            .map_or(0, |from| from + 1)
+    }
+
+    /// Runs every migration needed to bring `state` to the current version,
+    /// returning whether anything ran.
+    pub fn migrate(&self, state: &mut SessionState) -> Result<bool, SessionError> {
+        let target = self.version();
+        if state.version() > target {
+            let message = format!(
+                "version {} is newer than supported version {}",
+                state.version(),
+                target
+            );
+            return Err(SessionError::InvalidSessionError(message));
+        }
+        let migrated = state.version() < target;
+        while state.version() < target {
+            let from = state.version();
+            let migration = self.migrations.get(&from).ok_or_else(|| {
+                SessionError::InvalidSessionError(format!("no migration from version {from}"))
+            })?;
+            migration(state)?;
+            state.set_version(from + 1);
+        }
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::SessionValue;
+
+    fn migrator() -> SessionMigrator {
+        SessionMigrator::new()
+            .register(0, |state| {
+                if let Some(name) = state.remove("name") {
+                    state.insert("username", name);
+                }
+                Ok(())
+            })
+            .register(1, |state| {
+                state.insert("theme", json!("light"));
+                Ok(())
+            })
+    }
+
+    #[test]
+    fn migrate_runs_each_pending_migration_in_order() {
+        let mut state = SessionState::default();
+        state.insert("name", json!("brandon"));
+
+        let migrated = migrator()
+            .migrate(&mut state)
+            .expect("expected migrate to succeed");
+        assert!(migrated);
+        assert_eq!(state.version(), 2);
+        assert_eq!(state.get("name"), None);
+        assert_eq!(
+            state.get("username"),
+            Some(&SessionValue::Json(json!("brandon")))
+        );
+        assert!(state.get("theme").is_some());
+    }
+
+    #[test]
+    fn migrate_rejects_state_newer_than_the_registry() {
+        let mut state = SessionState::default();
+        state.set_version(5);
+        let result = migrator().migrate(&mut state);
+        assert!(matches!(result, Err(SessionError::InvalidSessionError(_))));
+    }
+}
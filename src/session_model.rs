@@ -1,27 +1,122 @@
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
 
+use rand::Rng;
 use serde::{de::DeserializeOwned, Serialize};
 
 use crate::{
+    instrument,
+    session_binding::{BindingCheck, SessionBinding},
     session_storage::{SessionStorageError, SessionStorageRead, SessionStorageWrite},
-    Session, SessionError, SessionKey,
+    Session, SessionError, SessionEvents, SessionField, SessionGuard, SessionKey, SessionLifecycle,
+    SessionLifecycleError, SessionMigrator, SessionScope, SessionStatus,
 };
 
+/// How [`SessionModel::save_with`] treats a session already stored under the
+/// key.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SaveMode {
+    /// Write whether or not the key is stored.
+    #[default]
+    Upsert,
+    /// Fail with [`SessionStorageError::ConflictError`] if the key is stored.
+    CreateOnly,
+    /// Fail with [`SessionStorageError::NotFoundError`] unless the key is
+    /// stored.
+    UpdateOnly,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SaveConflictPolicy {
+    /// Replace whatever is stored under the key.
+    #[default]
+    Overwrite,
+    /// Fail the save with [`SessionStorageError::ConflictError`].
+    Error,
+    /// Generate a new key and retry, up to the given number of times.
+    Regenerate(usize),
+}
+
+/// Sessions expire after `idle` without activity, and never live longer than
+/// `absolute` after they were created.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ExpirationPolicy {
+    pub idle: Duration,
+    pub absolute: Duration,
+}
+
 pub struct SessionModel<S> {
     storage: S,
     session: Session,
     duration: Duration,
+    persisted: bool,
+    conflict_policy: SaveConflictPolicy,
+    expiration_policy: Option<ExpirationPolicy>,
+    optimistic_locking: bool,
+    events: SessionEvents,
+    max_session_bytes: Option<usize>,
+    timeout_jitter: Option<f64>,
+    lifecycle_timeouts: HashMap<SessionLifecycle, Duration>,
+    last_activity: SystemTime,
+    touch_interval: Duration,
+    logged_out: bool,
 }
 
+const DEFAULT_TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
 impl<S> SessionModel<S> {
     pub fn new(storage: S, duration: Duration) -> Self {
         Self {
             storage,
             duration,
             session: Default::default(),
+            persisted: false,
+            conflict_policy: Default::default(),
+            expiration_policy: None,
+            optimistic_locking: false,
+            events: Default::default(),
+            max_session_bytes: None,
+            timeout_jitter: None,
+            lifecycle_timeouts: HashMap::new(),
+            last_activity: SystemTime::now(),
+            touch_interval: DEFAULT_TOUCH_INTERVAL,
+            logged_out: false,
         }
     }
 
+    /// Wraps a session read from `storage`, recording the access.
+    pub(crate) fn loaded(storage: S, mut session: Session, duration: Duration) -> Self {
+        let last_activity = session
+            .last_accessed()
+            .unwrap_or_else(|| session.created_at());
+        session.clear_replaces();
+        session.set_ttl(None);
+        // Sessions stored without metadata get their creation time on first
+        // load, so it has to be stored for the absolute lifetime to hold.
+        session.store_legacy_metadata();
+        session.record_access();
+        let mut model = Self {
+            storage,
+            session,
+            duration,
+            persisted: true,
+            conflict_policy: Default::default(),
+            expiration_policy: None,
+            optimistic_locking: false,
+            events: Default::default(),
+            max_session_bytes: None,
+            timeout_jitter: None,
+            lifecycle_timeouts: HashMap::new(),
+            last_activity,
+            touch_interval: DEFAULT_TOUCH_INTERVAL,
+            logged_out: false,
+        };
+        model.touch();
+        model
+    }
+
     pub fn id(&self) -> &SessionKey {
         self.session.id()
     }
@@ -30,8 +125,150 @@ impl<S> SessionModel<S> {
         &self.session
     }
 
+    /// The remaining lifetime of the session. Under an [`ExpirationPolicy`]
+    /// this never extends past the absolute lifetime.
     pub fn timeout(&self) -> Duration {
-        self.duration
+        match self.expiration_policy {
+            Some(policy) => self
+                .duration
+                .min(policy.absolute.saturating_sub(self.age())),
+            None => self.duration,
+        }
+    }
+
+    /// Overrides the TTL for this session only, e.g. for "remember me"
+    /// sessions. The TTL is stored with the session so storage can apply it.
+    pub fn set_timeout(&mut self, timeout: Duration) -> Result<(), SessionError> {
+        self.session.set_timeout(timeout)?;
+        self.duration = timeout;
+        Ok(())
+    }
+
+    /// When enabled, [`SessionModel::save`] fails with
+    /// [`SessionStorageError::RevisionConflictError`] if the stored session was
+    /// saved by someone else after this one was loaded. Checking costs an extra
+    /// read per save.
+    ///
+    /// The check reads the stored revision and then writes in a separate
+    /// storage call, so a save that lands between the two is still
+    /// overwritten. This narrows lost updates between requests but does not
+    /// rule them out; storage with compare-and-swap is needed for that.
+    pub fn set_optimistic_locking(&mut self, enabled: bool) {
+        self.optimistic_locking = enabled;
+    }
+
+    /// Sets the handlers notified when this session is created, saved,
+    /// destroyed or expires.
+    pub fn set_events(&mut self, events: SessionEvents) {
+        self.events = events;
+    }
+
+    /// Shortens the TTL passed to storage by a random fraction of up to
+    /// `jitter` (between 0 and 1) on every save, so sessions created together
+    /// do not all expire together. Storage sees it through
+    /// [`crate::SessionMetadata::ttl`]; the session's own timeout is left as is.
+    pub fn set_timeout_jitter(&mut self, jitter: f64) {
+        self.timeout_jitter = Some(jitter.clamp(0.0, 1.0));
+    }
+
+    /// Sets the TTL applied when the session moves to `lifecycle`, e.g. a
+    /// short one for [`SessionLifecycle::Elevated`]. Moving on to a lifecycle
+    /// without a TTL of its own restores the TTL the session had before.
+    pub fn set_lifecycle_timeout(&mut self, lifecycle: SessionLifecycle, timeout: Duration) {
+        self.lifecycle_timeouts.insert(lifecycle, timeout);
+    }
+
+    pub fn max_session_bytes(&self) -> Option<usize> {
+        self.max_session_bytes
+    }
+
+    /// Limits the size of the session, measured as serialized JSON. Saves of
+    /// larger sessions fail with [`SessionStorageError::PayloadTooLargeError`].
+    ///
+    /// The size is an estimate: storage that encodes sessions differently,
+    /// e.g. with a binary format or compression, may store more or fewer
+    /// bytes, so leave headroom below any hard limit of the backend.
+    pub fn set_max_session_bytes(&mut self, limit: usize) {
+        self.max_session_bytes = Some(limit);
+    }
+
+    pub fn expiration_policy(&self) -> Option<ExpirationPolicy> {
+        self.expiration_policy
+    }
+
+    pub fn set_expiration_policy(&mut self, policy: ExpirationPolicy) {
+        self.duration = policy.idle;
+        self.expiration_policy = Some(policy);
+    }
+
+    /// Whether the session has outlived the absolute lifetime of its
+    /// [`ExpirationPolicy`], or went unused for longer than its idle lifetime.
+    pub fn is_expired(&self) -> bool {
+        self.expiration_policy
+            .is_some_and(|policy| self.age() >= policy.absolute || self.idle_for() >= policy.idle)
+    }
+
+    /// How long the session went unused before this request, measured from
+    /// its previous load or, if it was never loaded, its creation. Loads are
+    /// stored at most once per [`SessionModel::set_touch_interval`], so this
+    /// may overstate idleness by up to that interval. Lets the UI sign users
+    /// out before storage expires the session.
+    pub fn idle_for(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.last_activity)
+            .unwrap_or_default()
+    }
+
+    pub fn is_idle(&self, threshold: Duration) -> bool {
+        self.idle_for() >= threshold
+    }
+
+    /// Loading a session marks it as changed if its last access was stored
+    /// more than `interval` ago, so the access is saved even by requests that
+    /// only read the session. Defaults to one minute; accesses in between are
+    /// only stored along with other changes.
+    pub fn set_touch_interval(&mut self, interval: Duration) {
+        self.touch_interval = interval;
+        self.touch();
+    }
+
+    fn touch(&mut self) {
+        let unchanged = self.session.status() == SessionStatus::Unchanged;
+        if self.persisted && unchanged && self.idle_for() >= self.touch_interval {
+            self.session.set_status(SessionStatus::Changed);
+        }
+    }
+
+    fn stored(&mut self) {
+        self.session.clear_replaces();
+        if !self.persisted {
+            self.events.create(&self.session);
+        }
+        self.persisted = true;
+        self.events.save(&self.session);
+    }
+
+    fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.session.created_at())
+            .unwrap_or_default()
+    }
+
+    /// Whether the session has been loaded from or written to storage.
+    pub fn is_persisted(&self) -> bool {
+        self.persisted
+    }
+
+    pub fn conflict_policy(&self) -> SaveConflictPolicy {
+        self.conflict_policy
+    }
+
+    /// Sets how [`SessionModel::save`] behaves when a session that has not
+    /// been stored yet collides with an existing key. The key is checked and
+    /// then written in separate storage calls, so a session stored under the
+    /// same key in between is still overwritten.
+    pub fn set_conflict_policy(&mut self, policy: SaveConflictPolicy) {
+        self.conflict_policy = policy;
     }
 
     pub fn insert<T: Serialize + DeserializeOwned>(
@@ -49,6 +286,98 @@ impl<S> SessionModel<S> {
     pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
         self.session.get(key)
     }
+
+    pub fn scope(&mut self, name: &str) -> Result<SessionScope<'_>, SessionError> {
+        self.session.scope(name)
+    }
+
+    pub fn get_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&str],
+    ) -> Result<Vec<Option<T>>, SessionError> {
+        self.session.get_many(keys)
+    }
+
+    pub fn extend<K, T, I>(&mut self, entries: I) -> Result<(), SessionError>
+    where
+        K: AsRef<str>,
+        T: Serialize,
+        I: IntoIterator<Item = (K, T)>,
+    {
+        self.session.extend(entries)
+    }
+
+    pub fn clear(&mut self) -> Result<(), SessionError> {
+        let base = self.session.base_timeout();
+        self.session.clear()?;
+        self.duration = base.unwrap_or(self.duration);
+        Ok(())
+    }
+
+    pub fn user_id(&self) -> Option<&str> {
+        self.session.user_id()
+    }
+
+    /// Whether this session was started in place of one that was logged out
+    /// with [`SessionModel::destroy_soft`], see
+    /// [`crate::SessionManager::load_or_create`].
+    pub fn was_logged_out(&self) -> bool {
+        self.logged_out
+    }
+
+    pub(crate) fn set_logged_out(&mut self) {
+        self.logged_out = true;
+    }
+
+    pub fn lifecycle(&self) -> SessionLifecycle {
+        self.session.lifecycle()
+    }
+
+    /// Whether the session was elevated recently enough to allow sensitive
+    /// actions.
+    pub fn is_elevated(&self) -> bool {
+        self.session.is_elevated()
+    }
+
+    pub fn bind(&mut self, binding: SessionBinding) -> Result<(), SessionError> {
+        self.session.bind(binding)
+    }
+
+    pub fn verify_binding(&self, fingerprint: &SessionBinding) -> BindingCheck {
+        self.session.verify_binding(fingerprint)
+    }
+
+    pub fn set_user_id(&mut self, user_id: Option<String>) -> Result<(), SessionError> {
+        self.session.set_user_id(user_id)
+    }
+
+    /// Brings the session to the migrator's schema version. Call this right
+    /// after [`SessionModel::new`] or [`SessionModel::load`]: stored sessions are
+    /// migrated, while sessions that have not been stored yet are stamped with
+    /// the current version.
+    pub fn migrate(&mut self, migrator: &SessionMigrator) -> Result<(), SessionError> {
+        if self.persisted {
+            self.session.migrate(migrator)
+        } else {
+            self.session.set_version(migrator.version());
+            Ok(())
+        }
+    }
+
+    pub fn insert_typed<F: SessionField>(
+        &mut self,
+        value: F::Value,
+    ) -> Result<Option<F::Value>, SessionError> {
+        self.session.insert_typed::<F>(&value)
+    }
+
+    pub fn remove_typed<F: SessionField>(&mut self) -> Result<Option<F::Value>, SessionError> {
+        self.session.remove_typed::<F>()
+    }
+
+    pub fn get_typed<F: SessionField>(&self) -> Result<Option<F::Value>, SessionError> {
+        self.session.get_typed::<F>()
+    }
 }
 
 impl<S> SessionModel<S>
@@ -59,29 +388,397 @@ where
         storage: S,
         id: &SessionKey,
     ) -> Result<Option<Self>, SessionStorageError<S::Error>> {
-        let session = storage.session_load(id)?;
-        let duration = storage.session_ttl(id)?;
-        let model = session.map(|session| Self {
-            storage,
-            session,
-            duration,
-        });
-        Ok(model)
+        let Some((session, duration)) = read_session(&storage, id)? else {
+            return Ok(None);
+        };
+        Ok(Some(Self::loaded(storage, session, duration)))
     }
 }
 
+/// Reads the session stored under `id` and the time it has left, for
+/// [`SessionModel::loaded`]. Tombstones fail with
+/// [`SessionStorageError::LoggedOutError`].
+pub(crate) fn read_session<S: SessionStorageRead>(
+    storage: &S,
+    id: &SessionKey,
+) -> Result<Option<(Session, Duration)>, SessionStorageError<S::Error>> {
+    let Some(session) = storage.session_load(id)? else {
+        return Ok(None);
+    };
+    if session.is_tombstone() {
+        return Err(SessionStorageError::LoggedOutError(id.clone()));
+    }
+    let duration = storage.session_ttl(id)?;
+    Ok(Some((session, duration)))
+}
+
 impl<S> SessionModel<S>
 where
-    S: SessionStorageWrite,
+    S: SessionStorageRead + SessionStorageWrite,
 {
+    /// Loads a single-use session, such as a magic-link or OAuth state, and
+    /// deletes it from storage in the same call, so it cannot be loaded
+    /// twice. The model lasts `duration` if it is saved again. Taking a
+    /// tombstone fails with [`SessionStorageError::LoggedOutError`]; the
+    /// session is read before it is taken so that a tombstone is left in
+    /// place with its TTL untouched.
+    pub fn take(
+        mut storage: S,
+        id: &SessionKey,
+        duration: Duration,
+    ) -> Result<Option<Self>, SessionStorageError<S::Error>> {
+        let stored = storage.session_load(id)?;
+        if stored.as_ref().is_some_and(Session::is_tombstone) {
+            return Err(SessionStorageError::LoggedOutError(id.clone()));
+        }
+        let Some(session) = storage.session_take(id)? else {
+            return Ok(None);
+        };
+        // Logged out in between: the key is gone either way, and restoring
+        // the tombstone would extend it.
+        if session.is_tombstone() {
+            return Err(SessionStorageError::LoggedOutError(id.clone()));
+        }
+        let mut model = Self::loaded(storage, session, duration);
+        model.persisted = false;
+        Ok(Some(model))
+    }
+
+    /// Loads the session and applies `policy`, destroying it and returning
+    /// `None` if it has outlived the absolute lifetime.
+    pub fn load_with_expiration(
+        storage: S,
+        id: &SessionKey,
+        policy: ExpirationPolicy,
+    ) -> Result<Option<Self>, SessionStorageError<S::Error>> {
+        let Some(mut model) = Self::load(storage, id)? else {
+            return Ok(None);
+        };
+        model.set_expiration_policy(policy);
+        if model.is_expired() {
+            model.destroy()?;
+            return Ok(None);
+        }
+        Ok(Some(model))
+    }
+
+    /// Loads the session presented by the client described by `fingerprint`.
+    /// Sessions bound to a different client are destroyed and `None` is
+    /// returned; unbound sessions are bound to the client.
+    pub fn load_with_binding(
+        storage: S,
+        id: &SessionKey,
+        fingerprint: &SessionBinding,
+    ) -> Result<Option<Self>, SessionStorageError<S::Error>> {
+        let Some(mut model) = Self::load(storage, id)? else {
+            return Ok(None);
+        };
+        match model.verify_binding(fingerprint) {
+            BindingCheck::Matched => {}
+            BindingCheck::Unbound => {
+                // A loaded session is never destroyed, so binding cannot fail.
+                let _ = model.bind(fingerprint.clone());
+            }
+            BindingCheck::Mismatched => {
+                model.destroy()?;
+                return Ok(None);
+            }
+        }
+        Ok(Some(model))
+    }
+
+    /// Returns a guard that saves the session when it goes out of scope.
+    pub fn guard(&mut self) -> SessionGuard<'_, S> {
+        SessionGuard::new(self)
+    }
+
+    /// Writes the session to storage. Sessions that have not changed since they
+    /// were loaded or last saved are not written.
+    ///
+    /// Saving needs to read storage as well as write it, to apply the
+    /// [`SaveConflictPolicy`] to new sessions and, if enabled, optimistic
+    /// locking to stored ones, so write-only storage cannot be saved to
+    /// through a model.
     pub fn save(&mut self) -> Result<(), SessionStorageError<S::Error>> {
-        self.storage.session_save(&mut self.session)?;
+        if self.is_unchanged() {
+            return Ok(());
+        }
+        self.check_size()?;
+        if !self.persisted {
+            self.resolve_conflict()?;
+        } else if self.optimistic_locking {
+            self.check_revision()?;
+        }
+        self.write()
+    }
+
+    /// Writes the session like [`SessionModel::save`], but checks for a stored
+    /// session under the key according to `mode` instead of the conflict
+    /// policy. The check and the write are separate storage calls, so
+    /// [`SaveMode::CreateOnly`] and [`SaveMode::UpdateOnly`] are not atomic: a
+    /// session stored or destroyed in between goes unnoticed.
+    pub fn save_with(&mut self, mode: SaveMode) -> Result<(), SessionStorageError<S::Error>> {
+        if self.is_unchanged() {
+            return Ok(());
+        }
+        self.check_size()?;
+        let id = self.session.id();
+        match mode {
+            SaveMode::Upsert => {}
+            SaveMode::CreateOnly => {
+                if self.storage.session_exists(id)? {
+                    return Err(SessionStorageError::ConflictError(id.clone()));
+                }
+            }
+            SaveMode::UpdateOnly => {
+                if !self.storage.session_exists(id)? {
+                    return Err(SessionStorageError::NotFoundError(id.clone()));
+                }
+            }
+        }
+        if self.persisted && self.optimistic_locking {
+            self.check_revision()?;
+        }
+        self.write()
+    }
+
+    fn is_unchanged(&self) -> bool {
+        let unchanged = self.session.status() != SessionStatus::Changed;
+        #[cfg(feature = "tracing")]
+        if unchanged {
+            tracing::trace!(key = %self.session.id(), "skipping save of unchanged session");
+        }
+        unchanged
+    }
+
+    fn write(&mut self) -> Result<(), SessionStorageError<S::Error>> {
+        if let Some(jitter) = self.timeout_jitter {
+            let ttl = self
+                .timeout()
+                .mul_f64(1.0 - rand::thread_rng().gen_range(0.0..=jitter));
+            self.session.set_ttl(Some(ttl));
+        }
+        let revision = self.session.revision();
+        self.session.set_revision(revision + 1);
+        let result = self.storage.session_save(&self.session);
+        self.session.set_ttl(None);
+        if let Err(e) = result {
+            self.session.set_revision(revision);
+            return Err(e);
+        }
+        self.session.set_status(SessionStatus::Unchanged);
+        self.stored();
+        Ok(())
+    }
+
+    /// Measures the session as JSON, whatever encoding storage uses, see
+    /// [`SessionModel::set_max_session_bytes`].
+    fn check_size(&self) -> Result<(), SessionStorageError<S::Error>> {
+        let Some(limit) = self.max_session_bytes else {
+            return Ok(());
+        };
+        let size = serde_json::to_vec(self.session.state())
+            .map_err(|_| SessionStorageError::SerializationError)?
+            .len();
+        if size > limit {
+            return Err(SessionStorageError::PayloadTooLargeError(size, limit));
+        }
+        Ok(())
+    }
+
+    fn check_revision(&self) -> Result<(), SessionStorageError<S::Error>> {
+        let id = self.session.id();
+        match self.storage.session_load(id)? {
+            Some(stored) if stored.revision() != self.session.revision() => {
+                Err(SessionStorageError::RevisionConflictError(id.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn resolve_conflict(&mut self) -> Result<(), SessionStorageError<S::Error>> {
+        let mut retries = match self.conflict_policy {
+            SaveConflictPolicy::Overwrite => return Ok(()),
+            SaveConflictPolicy::Error => 0,
+            SaveConflictPolicy::Regenerate(retries) => retries,
+        };
+        while self.storage.session_exists(self.session.id())? {
+            if retries == 0 {
+                let id = self.session.id().clone();
+                return Err(SessionStorageError::ConflictError(id));
+            }
+            retries -= 1;
+            // The colliding key belongs to another session, so it must not be
+            // recorded as this session's previous key.
+            self.session.replace_key();
+        }
+        Ok(())
+    }
+
+    /// Moves the session to a newly generated key and deletes the previous one,
+    /// returning the new key. Call this after login to prevent session fixation.
+    /// The session is written like [`SessionModel::save`]. Once it is stored
+    /// under the new key the rotation has happened, so failing to delete the
+    /// previous key is only logged, and that copy is left to expire.
+    pub fn regenerate(&mut self) -> Result<SessionKey, SessionStorageError<S::Error>> {
+        let previous = self.session.id().clone();
+        self.check_size()?;
+        if self.persisted && self.optimistic_locking {
+            self.check_revision()?;
+        }
+        self.session
+            .regenerate()
+            .map_err(|e| SessionStorageError::from_regenerate(&previous, e))?;
+        let persisted = self.persisted;
+        self.write()?;
+        if persisted {
+            self.destroy_replaced(&previous);
+        }
+        Ok(self.session.id().clone())
+    }
+
+    fn destroy_replaced(&mut self, previous: &SessionKey) {
+        // Retry once, as a surviving copy keeps the old key usable until it
+        // expires.
+        let destroyed = self.storage.session_destroy(previous).is_ok()
+            || self.storage.session_destroy(previous).is_ok();
+        if !destroyed {
+            instrument::warn(previous, "failed to delete the session's previous key");
+        }
+    }
+
+    /// Logs `user_id` in, moving the session to
+    /// [`SessionLifecycle::Authenticated`] under a new key.
+    pub fn authenticate(
+        &mut self,
+        user_id: String,
+    ) -> Result<SessionKey, SessionLifecycleError<S::Error>> {
+        self.session.set_user_id(Some(user_id))?;
+        self.transition(SessionLifecycle::Authenticated, None)
+    }
+
+    /// Moves an authenticated session to [`SessionLifecycle::Elevated`] for
+    /// `duration` under a new key. Call this after the user has re-entered
+    /// their credentials, and check [`SessionModel::is_elevated`] before
+    /// sensitive actions.
+    pub fn elevate(
+        &mut self,
+        duration: Duration,
+    ) -> Result<SessionKey, SessionLifecycleError<S::Error>> {
+        let from = self.lifecycle();
+        if from == SessionLifecycle::Anonymous {
+            let to = SessionLifecycle::Elevated;
+            return Err(SessionLifecycleError::InvalidTransitionError(from, to));
+        }
+        let until = SystemTime::now() + duration;
+        self.transition(SessionLifecycle::Elevated, Some(until))
+    }
+
+    /// Steps the session down one level under a new key. Dropping to
+    /// [`SessionLifecycle::Anonymous`] logs the user out.
+    pub fn downgrade(&mut self) -> Result<SessionKey, SessionLifecycleError<S::Error>> {
+        let to = match self.lifecycle() {
+            SessionLifecycle::Elevated => SessionLifecycle::Authenticated,
+            SessionLifecycle::Authenticated => {
+                self.session.set_user_id(None)?;
+                SessionLifecycle::Anonymous
+            }
+            from @ SessionLifecycle::Anonymous => {
+                let to = SessionLifecycle::Anonymous;
+                return Err(SessionLifecycleError::InvalidTransitionError(from, to));
+            }
+        };
+        self.transition(to, None)
+    }
+
+    fn transition(
+        &mut self,
+        lifecycle: SessionLifecycle,
+        elevated_until: Option<SystemTime>,
+    ) -> Result<SessionKey, SessionLifecycleError<S::Error>> {
+        self.session.set_lifecycle(lifecycle, elevated_until)?;
+        match self.lifecycle_timeouts.get(&lifecycle).copied() {
+            Some(timeout) => {
+                if self.session.base_timeout().is_none() {
+                    let base = self.session.timeout().unwrap_or(self.duration);
+                    self.session.set_base_timeout(Some(base));
+                }
+                self.set_timeout(timeout)?;
+            }
+            None => {
+                if let Some(base) = self.session.base_timeout() {
+                    self.set_timeout(base)?;
+                    self.session.set_base_timeout(None);
+                }
+            }
+        }
+        Ok(self.regenerate()?)
+    }
+}
+
+impl<S> SessionModel<S>
+where
+    S: SessionStorageWrite,
+{
+    /// Stores a copy of the session under a new key that lasts `timeout`,
+    /// e.g. for impersonation or previews, and returns its key. The copy and
+    /// this session change independently from then on. The copy starts out
+    /// anonymous and unbound, so it has to be authenticated on its own.
+    pub fn fork(&mut self, timeout: Duration) -> Result<SessionKey, SessionStorageError<S::Error>> {
+        let mut state = self.session.state().clone();
+        state.metadata_mut().reset_identity();
+        let id = self.session.id().clone();
+        let mut fork = Session::new(id.clone(), state);
+        fork.regenerate()
+            .map_err(|e| SessionStorageError::from_regenerate(&id, e))?;
+        fork.clear_replaces();
+        fork.set_revision(0);
+        // A session that was just created cannot be destroyed.
+        let _ = fork.set_timeout(timeout);
+        self.storage.session_save(&fork)?;
+        Ok(fork.id().clone())
+    }
+
+    /// Deletes the stored session and starts over with an empty session under
+    /// a new key, returning the new key. The new session is stored on the next
+    /// [`SessionModel::save`].
+    pub fn renew(&mut self) -> Result<SessionKey, SessionStorageError<S::Error>> {
+        if self.persisted {
+            self.storage.session_destroy(self.session.id())?;
+            self.events.destroy(&self.session);
+        }
+        let base = self.session.base_timeout();
+        self.session.renew();
+        self.duration = base.unwrap_or(self.duration);
+        self.persisted = false;
+        Ok(self.session.id().clone())
+    }
+
+    /// Replaces the stored session with a tombstone kept for `grace`, so
+    /// loading it fails with [`SessionStorageError::LoggedOutError`] instead
+    /// of finding nothing, and the user can be told they were logged out
+    /// rather than that their session expired.
+    pub fn destroy_soft(&mut self, grace: Duration) -> Result<(), SessionStorageError<S::Error>> {
+        let mut tombstone = Session::new(self.session.id().clone(), Default::default());
+        tombstone.tombstone(grace);
+        self.storage.session_save(&tombstone)?;
+        self.session.set_status(SessionStatus::Destroyed);
+        self.events.destroy(&self.session);
         Ok(())
     }
 
+    /// Deletes the stored session. Handlers are notified with
+    /// [`crate::SessionEventHandler::on_expire`] instead of `on_destroy` if the
+    /// session has expired.
     pub fn destroy(&mut self) -> Result<(), SessionStorageError<S::Error>> {
         let id = self.session.id();
         self.storage.session_destroy(id)?;
+        self.session.set_status(SessionStatus::Destroyed);
+        if self.is_expired() {
+            self.events.expire(&self.session);
+        } else {
+            self.events.destroy(&self.session);
+        }
         Ok(())
     }
 }
@@ -94,12 +791,22 @@ impl<S> From<SessionModel<S>> for Session {
 
 #[cfg(test)]
 mod test {
-    use std::{borrow::Cow, collections::HashMap, time::Duration};
+    use std::{
+        borrow::Cow,
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
 
     use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
 
+    use super::{ExpirationPolicy, SaveConflictPolicy, SaveMode, SessionBinding};
     use crate::{
-        session_state::SessionState, session_storage::SessionStateTable, SessionKey, SessionModel,
+        session_state::SessionState,
+        session_storage::{SessionStateTable, SessionStorageError, SessionStorageWrite},
+        test_util::{MockOperation, MockStorage},
+        Session, SessionEventHandler, SessionEvents, SessionKey, SessionLifecycle,
+        SessionLifecycleError, SessionMigrator, SessionModel, SessionStatus,
     };
 
     struct TestStorage {
@@ -168,7 +875,7 @@ mod test {
             .expect("Failed to retrieve state from storage")
             .expect("Expected state to be present");
         let id = state.get("id").expect("Expected id to be present");
-        assert_eq!(id, "\"abc\"");
+        assert_eq!(id.as_json(), Some(&serde_json::json!("abc")));
     }
 
     #[test]
@@ -197,7 +904,7 @@ mod test {
         let mut storage = TestStorage::new();
         let key = SessionKey::generate();
         let mut state = SessionState::default();
-        state.insert("id", "abc".to_string());
+        state.insert("id", serde_json::json!("abc"));
         storage
             .insert(&key, &state)
             .expect("Failed to insert session state");
@@ -208,4 +915,726 @@ mod test {
         let retrieved = storage.get(&key).expect("Failed to get session state");
         assert!(retrieved.is_none())
     }
+
+    #[test]
+    fn migrate_upgrades_a_loaded_session() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        let migrator = SessionMigrator::new().register(0, |state| {
+            state.insert("migrated", serde_json::json!(true));
+            Ok(())
+        });
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.migrate(&migrator).expect("Failed to migrate session");
+        let migrated = model
+            .get::<bool>("migrated")
+            .expect("Failed to read from session model");
+        assert_eq!(migrated, Some(true));
+        assert_eq!(model.session().state().version(), 1);
+        assert_eq!(model.session().status(), SessionStatus::Changed);
+    }
+
+    #[test]
+    fn load_with_expiration_destroys_sessions_past_their_absolute_lifetime() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        let policy = ExpirationPolicy {
+            idle: Duration::from_secs(100),
+            absolute: Duration::ZERO,
+        };
+
+        let model = SessionModel::load_with_expiration(&mut storage, &id, policy)
+            .expect("Failed to load session model");
+        assert!(model.is_none());
+        let state = storage.get(&id).expect("Failed to get session state");
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn load_with_expiration_destroys_idle_sessions() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        std::thread::sleep(Duration::from_millis(20));
+        let policy = ExpirationPolicy {
+            idle: Duration::from_millis(20),
+            absolute: Duration::from_secs(100),
+        };
+
+        let model = SessionModel::load_with_expiration(&mut storage, &id, policy)
+            .expect("Failed to load session model");
+        assert!(model.is_none());
+        let state = storage.get(&id).expect("Failed to get session state");
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn load_stores_the_creation_time_of_legacy_sessions() {
+        let mut storage = TestStorage::new();
+        let id = SessionKey::generate();
+        let state = serde_json::from_str::<SessionState>(r#"{"id":"\"abc\""}"#)
+            .expect("Failed to parse legacy session state");
+        storage
+            .insert(&id, &state)
+            .expect("Failed to insert session state");
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        assert_eq!(model.session().status(), SessionStatus::Changed);
+        let created_at = model.session().created_at();
+        model.save().expect("Failed to save session model");
+        drop(model);
+
+        let model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        assert_eq!(model.session().created_at(), created_at);
+        assert_eq!(model.session().status(), SessionStatus::Unchanged);
+    }
+
+    #[test]
+    fn timeout_is_capped_by_the_absolute_lifetime() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_expiration_policy(ExpirationPolicy {
+            idle: Duration::from_secs(1800),
+            absolute: Duration::from_secs(60),
+        });
+        assert!(!model.is_expired());
+        assert!(model.timeout() <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn set_timeout_stores_the_timeout_with_the_session() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .set_timeout(Duration::from_secs(2_592_000))
+            .expect("Failed to set timeout");
+        assert_eq!(model.timeout(), Duration::from_secs(2_592_000));
+        model.save().expect("Failed to save session model");
+        let id = model.id().clone();
+
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected session state to be present");
+        assert_eq!(
+            state.metadata().timeout(),
+            Some(Duration::from_secs(2_592_000))
+        );
+    }
+
+    #[test]
+    fn guard_saves_the_session_when_dropped() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        {
+            let mut guard = model.guard();
+            guard
+                .insert::<String>("id", "abc".to_string())
+                .expect("Failed to write to session model");
+        }
+        let id = model.id().clone();
+
+        let state = storage.get(&id).expect("Failed to get session state");
+        assert!(state.is_some());
+    }
+
+    #[test]
+    fn guard_does_not_save_the_session_when_dropped_in_a_panic() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        let id = model.id().clone();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = model.guard();
+            guard
+                .insert::<String>("id", "abc".to_string())
+                .expect("Failed to write to session model");
+            panic!("handler failed");
+        }));
+        assert!(result.is_err());
+
+        let state = storage.get(&id).expect("Failed to get session state");
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn save_with_optimistic_locking_rejects_a_stale_session() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        let mut stale = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present")
+            .session;
+        stale
+            .insert("id", &"stale".to_string())
+            .expect("Failed to write to session");
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model
+            .insert::<String>("id", "fresh".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.session = stale;
+        model.set_optimistic_locking(true);
+        let result = model.save();
+        assert!(
+            matches!(result, Err(SessionStorageError::RevisionConflictError(key)) if key == id)
+        );
+        for mode in [SaveMode::Upsert, SaveMode::UpdateOnly] {
+            let result = model.save_with(mode);
+            assert!(
+                matches!(result, Err(SessionStorageError::RevisionConflictError(key)) if key == id)
+            );
+        }
+    }
+
+    #[test]
+    fn load_with_binding_rejects_a_different_client() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        let client = SessionBinding {
+            ip: Some("10.0.0.1".to_string()),
+            user_agent: None,
+        };
+        let mut model = SessionModel::load_with_binding(&mut storage, &id, &client)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.save().expect("Failed to save session model");
+
+        let attacker = SessionBinding {
+            ip: Some("10.0.0.2".to_string()),
+            user_agent: None,
+        };
+        let model = SessionModel::load_with_binding(&mut storage, &id, &attacker)
+            .expect("Failed to load session model");
+        assert!(model.is_none());
+        let state = storage.get(&id).expect("Failed to get session state");
+        assert!(state.is_none());
+    }
+
+    #[test]
+    fn save_skips_an_unchanged_session() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.save().expect("Failed to save session model");
+        let key = model.id().clone();
+
+        let state = storage.get(&key).expect("Failed to get session state");
+        assert!(state.is_none());
+    }
+
+    fn save_existing(storage: &mut TestStorage) -> SessionKey {
+        let mut model = SessionModel::new(storage, Duration::from_secs(100));
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+        model.id().clone()
+    }
+
+    #[test]
+    fn save_with_error_policy_rejects_an_existing_key() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.session = Session::new(id.clone(), SessionState::default());
+        model
+            .insert::<String>("id", "def".to_string())
+            .expect("Failed to write to session model");
+        model.set_conflict_policy(SaveConflictPolicy::Error);
+        let result = model.save();
+        assert!(matches!(result, Err(SessionStorageError::ConflictError(key)) if key == id));
+    }
+
+    #[test]
+    fn save_with_regenerate_policy_stores_under_a_new_key() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.session = Session::new(id.clone(), SessionState::default());
+        model
+            .insert::<String>("id", "def".to_string())
+            .expect("Failed to write to session model");
+        model.set_conflict_policy(SaveConflictPolicy::Regenerate(1));
+        model.save().expect("Failed to save session model");
+        let new_id = model.id().clone();
+        assert_ne!(new_id, id);
+        let stored = storage
+            .get(&new_id)
+            .expect("Failed to retrieve state from storage")
+            .expect("Expected new state to be present");
+        assert_eq!(stored.metadata().replaces(), None);
+
+        let state = storage
+            .get(&id)
+            .expect("Failed to retrieve state from storage")
+            .expect("Expected original state to be present");
+        assert_eq!(
+            state
+                .get("id")
+                .expect("Expected id to be present")
+                .as_json(),
+            Some(&serde_json::json!("abc"))
+        );
+    }
+
+    #[test]
+    fn regenerate_moves_the_session_to_a_new_key() {
+        let mut storage = TestStorage::new();
+        let previous = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &previous)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        let id = model.regenerate().expect("Failed to regenerate session");
+        assert_ne!(id, previous);
+
+        let old = storage.get(&previous).expect("Failed to get session state");
+        assert!(old.is_none());
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected session state to be present");
+        assert_eq!(
+            state
+                .get("id")
+                .expect("Expected id to be present")
+                .as_json(),
+            Some(&serde_json::json!("abc"))
+        );
+    }
+
+    #[test]
+    fn regenerate_does_not_store_a_destroyed_session() {
+        let mut storage = TestStorage::new();
+        let previous = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &previous)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.destroy().expect("Failed to destroy session");
+        let result = model.regenerate();
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::DestroyedError(key)) if key == previous
+        ));
+        assert!(storage.map.is_empty());
+    }
+
+    #[test]
+    fn regenerate_bumps_the_revision() {
+        let mut storage = TestStorage::new();
+        let previous = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &previous)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        let revision = model.session().revision();
+        let id = model.regenerate().expect("Failed to regenerate session");
+
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected session state to be present");
+        assert_eq!(state.metadata().revision(), revision + 1);
+    }
+
+    #[test]
+    fn lifecycle_transitions_rekey_and_apply_timeouts() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_lifecycle_timeout(SessionLifecycle::Elevated, Duration::from_secs(10));
+        assert!(matches!(
+            model.elevate(Duration::from_secs(300)),
+            Err(SessionLifecycleError::InvalidTransitionError(
+                SessionLifecycle::Anonymous,
+                SessionLifecycle::Elevated
+            ))
+        ));
+
+        let anonymous = model.id().clone();
+        let authenticated = model
+            .authenticate("fred".to_string())
+            .expect("Failed to authenticate session");
+        assert_ne!(authenticated, anonymous);
+        assert_eq!(model.user_id(), Some("fred"));
+        let elevated = model
+            .elevate(Duration::from_secs(300))
+            .expect("Failed to elevate session");
+        assert_ne!(elevated, authenticated);
+        assert_eq!(model.lifecycle(), SessionLifecycle::Elevated);
+        assert_eq!(model.timeout(), Duration::from_secs(10));
+        assert!(model.is_elevated());
+
+        model.downgrade().expect("Failed to downgrade session");
+        assert_eq!(model.lifecycle(), SessionLifecycle::Authenticated);
+        assert_eq!(model.timeout(), Duration::from_secs(100));
+        model.downgrade().expect("Failed to downgrade session");
+        assert_eq!(model.lifecycle(), SessionLifecycle::Anonymous);
+        assert_eq!(model.user_id(), None);
+        let id = model.id().clone();
+        drop(model);
+        assert_eq!(storage.map.len(), 1);
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected session state to be present");
+        assert_eq!(state.metadata().lifecycle(), SessionLifecycle::Anonymous);
+    }
+
+    #[test]
+    fn renew_starts_an_anonymous_session() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_lifecycle_timeout(SessionLifecycle::Elevated, Duration::from_secs(10));
+        model
+            .authenticate("fred".to_string())
+            .expect("Failed to authenticate session");
+        model
+            .elevate(Duration::from_secs(300))
+            .expect("Failed to elevate session");
+        model.renew().expect("Failed to renew session");
+
+        assert_eq!(model.lifecycle(), SessionLifecycle::Anonymous);
+        assert_eq!(model.user_id(), None);
+        assert!(!model.is_elevated());
+        assert_eq!(model.session().state().metadata().elevated_until(), None);
+        assert_eq!(model.timeout(), Duration::from_secs(100));
+    }
+
+    #[test]
+    fn elevation_runs_out_after_its_duration() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .authenticate("fred".to_string())
+            .expect("Failed to authenticate session");
+        model
+            .elevate(Duration::ZERO)
+            .expect("Failed to elevate session");
+        assert_eq!(model.lifecycle(), SessionLifecycle::Elevated);
+        assert!(!model.is_elevated());
+
+        model
+            .elevate(Duration::from_secs(300))
+            .expect("Failed to elevate session");
+        assert!(model.is_elevated());
+        model.downgrade().expect("Failed to downgrade session");
+        assert!(!model.is_elevated());
+        assert_eq!(model.session().state().metadata().elevated_until(), None);
+    }
+
+    #[test]
+    fn session_model_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SessionModel<TestStorage>>();
+    }
+
+    #[test]
+    fn events_are_notified_of_the_session_lifecycle() {
+        struct Recorder(Arc<Mutex<Vec<&'static str>>>);
+
+        impl SessionEventHandler for Recorder {
+            fn on_create(&self, _session: &Session) {
+                self.0.lock().unwrap().push("create");
+            }
+
+            fn on_save(&self, _session: &Session) {
+                self.0.lock().unwrap().push("save");
+            }
+
+            fn on_destroy(&self, _session: &Session) {
+                self.0.lock().unwrap().push("destroy");
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut events = SessionEvents::new();
+        events.register(Recorder(log.clone()));
+
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_events(events);
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+        model
+            .insert::<String>("id", "def".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+        model.destroy().expect("Failed to destroy session model");
+
+        let log = log.lock().unwrap();
+        assert_eq!(*log, vec!["create", "save", "save", "destroy"]);
+    }
+
+    #[test]
+    fn save_rejects_sessions_over_the_size_limit() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_max_session_bytes(64);
+        model
+            .insert::<String>("id", "a".repeat(100))
+            .expect("Failed to write to session model");
+        let result = model.save();
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::PayloadTooLargeError(_, 64))
+        ));
+        assert!(storage.map.is_empty());
+    }
+
+    #[test]
+    fn load_records_the_access() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        let session = model.session();
+        assert_eq!(session.access_count(), 1);
+        assert!(session.last_accessed() >= Some(session.created_at()));
+    }
+
+    #[test]
+    fn take_loads_a_session_only_once() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let model = SessionModel::take(&mut storage, &id, Duration::from_secs(100))
+            .expect("Failed to take session model")
+            .expect("Expected session model to be present");
+        let value = model.get::<String>("id").expect("Failed to get id");
+        assert_eq!(value, Some("abc".to_string()));
+        assert!(!model.is_persisted());
+        let again = SessionModel::take(&mut storage, &id, Duration::from_secs(100))
+            .expect("Failed to take session model");
+        assert!(again.is_none());
+    }
+
+    #[test]
+    fn take_leaves_a_tombstone_untouched() {
+        let mut storage = MockStorage::new();
+        let mut tombstone = Session::default();
+        tombstone.tombstone(Duration::from_secs(60));
+        storage
+            .session_save(&tombstone)
+            .expect("Failed to save tombstone");
+
+        let result = SessionModel::take(&mut storage, tombstone.id(), Duration::from_secs(100));
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::LoggedOutError(key)) if &key == tombstone.id()
+        ));
+        assert_eq!(storage.call_count(MockOperation::Insert), 1);
+        storage.assert_not_called(MockOperation::Remove);
+    }
+
+    #[test]
+    fn fork_does_not_copy_the_identity() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .authenticate("fred".to_string())
+            .expect("Failed to authenticate session");
+        model
+            .elevate(Duration::from_secs(300))
+            .expect("Failed to elevate session");
+        model
+            .bind(SessionBinding {
+                ip: Some("10.0.0.1".to_string()),
+                user_agent: None,
+            })
+            .expect("Failed to bind session");
+        let fork = model
+            .fork(Duration::from_secs(60))
+            .expect("Failed to fork session");
+        assert_eq!(model.user_id(), Some("fred"));
+        drop(model);
+
+        let state = storage
+            .get(&fork)
+            .expect("Failed to get session state")
+            .expect("Expected forked state to be present");
+        let metadata = state.metadata();
+        assert_eq!(metadata.user_id(), None);
+        assert_eq!(metadata.lifecycle(), SessionLifecycle::Anonymous);
+        assert_eq!(metadata.elevated_until(), None);
+        assert_eq!(metadata.binding(), None);
+        assert_eq!(metadata.timeout(), Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn fork_stores_a_copy_under_a_new_key() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        let fork = model
+            .fork(Duration::from_secs(60))
+            .expect("Failed to fork session");
+        assert_ne!(fork, id);
+        drop(model);
+
+        let state = storage
+            .get(&fork)
+            .expect("Failed to get session state")
+            .expect("Expected forked state to be present");
+        assert_eq!(state.metadata().timeout(), Some(Duration::from_secs(60)));
+        assert_eq!(
+            state.get("id").and_then(|value| value.as_json()),
+            Some(&serde_json::json!("abc"))
+        );
+        assert!(storage.map.contains_key(&id));
+    }
+
+    #[test]
+    fn destroy_soft_leaves_a_tombstone() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model
+            .destroy_soft(Duration::from_secs(60))
+            .expect("Failed to soft-destroy session");
+        assert_eq!(model.session().status(), SessionStatus::Destroyed);
+        drop(model);
+
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected tombstone to be present");
+        assert!(state.metadata().is_tombstone());
+        assert!(state.get("id").is_none());
+        let result = SessionModel::load(&mut storage, &id);
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::LoggedOutError(key)) if key == id
+        ));
+        let result = SessionModel::take(&mut storage, &id, Duration::from_secs(60));
+        assert!(matches!(
+            result,
+            Err(SessionStorageError::LoggedOutError(key)) if key == id
+        ));
+        assert!(storage.map.contains_key(&id));
+    }
+
+    #[test]
+    fn idle_for_measures_from_the_previous_access() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        std::thread::sleep(Duration::from_millis(20));
+
+        let model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        assert!(model.idle_for() >= Duration::from_millis(20));
+        assert!(model.is_idle(Duration::from_millis(20)));
+        assert!(!model.is_idle(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn idle_for_counts_read_only_loads() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.set_touch_interval(Duration::from_millis(50));
+        model.save().expect("Failed to save session model");
+        drop(model);
+
+        let model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        assert!(model.idle_for() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn load_marks_a_stale_access_for_saving() {
+        let mut storage = TestStorage::new();
+        let id = save_existing(&mut storage);
+
+        let mut model = SessionModel::load(&mut storage, &id)
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        assert_eq!(model.session().status(), SessionStatus::Unchanged);
+        model.set_touch_interval(Duration::ZERO);
+        assert_eq!(model.session().status(), SessionStatus::Changed);
+        model.save().expect("Failed to save session model");
+        drop(model);
+
+        let state = storage
+            .get(&id)
+            .expect("Failed to get session state")
+            .expect("Expected session state to be present");
+        assert!(state.metadata().last_accessed().is_some());
+        assert_eq!(state.metadata().access_count(), 1);
+    }
+
+    #[test]
+    fn save_applies_timeout_jitter() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model.set_timeout_jitter(0.5);
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+        assert_eq!(model.session().timeout(), None);
+        assert_eq!(model.session().state().metadata().ttl(), None);
+        let key = model.id().clone();
+
+        let state = storage
+            .get(&key)
+            .expect("Failed to retrieve state from storage")
+            .expect("Expected state to be present");
+        let ttl = state.metadata().ttl().expect("Expected a ttl to be stored");
+        assert!((Duration::from_secs(50)..=Duration::from_secs(100)).contains(&ttl));
+        assert_eq!(state.metadata().timeout(), None);
+    }
+
+    #[test]
+    fn save_with_respects_the_save_mode() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        let result = model.save_with(SaveMode::UpdateOnly);
+        assert!(matches!(result, Err(SessionStorageError::NotFoundError(_))));
+
+        model
+            .save_with(SaveMode::CreateOnly)
+            .expect("Failed to save session model");
+        model
+            .insert::<String>("id", "def".to_string())
+            .expect("Failed to write to session model");
+        let result = model.save_with(SaveMode::CreateOnly);
+        assert!(matches!(result, Err(SessionStorageError::ConflictError(_))));
+        model
+            .save_with(SaveMode::UpdateOnly)
+            .expect("Failed to save session model");
+    }
 }
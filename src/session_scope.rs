@@ -0,0 +1,114 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{Session, SessionError};
+
+/// A view of the keys under one namespace of a session, obtained from
+/// [`Session::scope`]. Keys are stored as `"{name}.{key}"`, so independent
+/// features cannot overwrite each other's values. Names cannot contain '.',
+/// so one scope never sees into another, such as `"a"` into `"a.b"`.
+///
+/// This is the layout [`crate::SessionSection`] uses, so a scope named after
+/// a section's `PREFIX` shares its keys with the section.
+pub struct SessionScope<'a> {
+    session: &'a mut Session,
+    prefix: String,
+}
+
+impl<'a> SessionScope<'a> {
+    pub(crate) fn new(session: &'a mut Session, name: &str) -> Result<Self, SessionError> {
+        if name.contains('.') {
+            return Err(SessionError::InvalidScopeError(name.to_string()));
+        }
+        let prefix = format!("{name}.");
+        Ok(Self { session, prefix })
+    }
+
+    pub fn name(&self) -> &str {
+        self.prefix.trim_end_matches('.')
+    }
+
+    pub fn insert<T: Serialize + DeserializeOwned>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<Option<T>, SessionError> {
+        let key = self.key(key);
+        self.session.insert(&key, value)
+    }
+
+    pub fn remove<T: DeserializeOwned>(&mut self, key: &str) -> Result<Option<T>, SessionError> {
+        let key = self.key(key);
+        self.session.remove(&key)
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, SessionError> {
+        self.session.get(&self.key(key))
+    }
+
+    /// The keys in this scope, without the scope's prefix.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.session
+            .keys()
+            .filter_map(|key| key.strip_prefix(self.prefix.as_str()))
+    }
+
+    /// Removes every key in this scope, leaving the rest of the session alone.
+    pub fn clear(&mut self) -> Result<(), SessionError> {
+        let keys = self
+            .session
+            .keys()
+            .filter(|key| key.starts_with(&self.prefix))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.session.remove_value(&key)?;
+        }
+        Ok(())
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Session, SessionError};
+
+    #[test]
+    fn scopes_do_not_see_each_others_keys() {
+        let mut session = Session::default();
+        session
+            .scope("cart")
+            .expect("expected scope \"cart\" to be valid")
+            .insert("items", &vec![1, 2])
+            .expect("expected insert \"items\" to succeed");
+        let mut auth = session
+            .scope("auth")
+            .expect("expected scope \"auth\" to be valid");
+        auth.insert("items", &"token".to_string())
+            .expect("expected insert \"items\" to succeed");
+        auth.clear().expect("expected clear to succeed");
+        assert_eq!(auth.keys().count(), 0);
+
+        let cart = session
+            .scope("cart")
+            .expect("expected scope \"cart\" to be valid");
+        assert_eq!(cart.keys().collect::<Vec<_>>(), vec!["items"]);
+        let items = cart
+            .get::<Vec<u32>>("items")
+            .expect("expected get \"items\" to succeed");
+        assert_eq!(items, Some(vec![1, 2]));
+        assert_eq!(session.keys().collect::<Vec<_>>(), vec!["cart.items"]);
+    }
+
+    #[test]
+    fn scope_names_cannot_contain_the_separator() {
+        let mut session = Session::default();
+        let result = session.scope("cart.items");
+        assert!(matches!(
+            result,
+            Err(SessionError::InvalidScopeError(name)) if name == "cart.items"
+        ));
+    }
+}
@@ -0,0 +1,28 @@
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A struct stored as a group of session keys, one per field, named
+/// `<PREFIX>.<field>`. See [`crate::Session::load_section`].
+///
+/// With the `derive` feature this can be derived, taking the prefix from
+/// `#[session(prefix = "...")]` or the struct name in snake case.
+///
+/// ```
+/// use lushus_session::{Session, SessionSection};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Cart {
+///     items: Vec<u64>,
+/// }
+///
+/// impl SessionSection for Cart {
+///     const PREFIX: &'static str = "cart";
+/// }
+///
+/// let mut session = Session::default();
+/// session.store_section(&Cart { items: vec![1] }).unwrap();
+/// assert_eq!(session.get::<Vec<u64>>("cart.items").unwrap(), Some(vec![1]));
+/// ```
+pub trait SessionSection: Serialize + DeserializeOwned {
+    const PREFIX: &'static str;
+}
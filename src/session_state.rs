@@ -1,18 +1,434 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt::Formatter};
 
-#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
-pub struct SessionState(HashMap<String, String>);
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{
+    de::{EnumAccess, Error, SeqAccess, VariantAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::SessionMetadata;
+
+/// A single stored session value.
+///
+/// Values are tagged with their kind. In human-readable formats JSON values
+/// are stored as nested JSON, so stored sessions can be inspected directly,
+/// and binary values as base64. Other formats store JSON values as JSON text
+/// and binary values as raw bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionValue {
+    Json(serde_json::Value),
+    Binary(Vec<u8>),
+}
+
+impl SessionValue {
+    pub fn as_json(&self) -> Option<&serde_json::Value> {
+        match self {
+            SessionValue::Json(value) => Some(value),
+            SessionValue::Binary(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            SessionValue::Json(_) => None,
+            SessionValue::Binary(value) => Some(value),
+        }
+    }
+}
+
+impl From<serde_json::Value> for SessionValue {
+    fn from(value: serde_json::Value) -> Self {
+        SessionValue::Json(value)
+    }
+}
+
+impl From<Vec<u8>> for SessionValue {
+    fn from(value: Vec<u8>) -> Self {
+        SessionValue::Binary(value)
+    }
+}
+
+const VARIANTS: &[&str] = &["json", "binary"];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Kind {
+    Json,
+    Binary,
+}
+
+impl Serialize for SessionValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let human_readable = serializer.is_human_readable();
+        match self {
+            SessionValue::Json(value) if human_readable => {
+                serializer.serialize_newtype_variant("SessionValue", 0, "json", value)
+            }
+            SessionValue::Json(value) => {
+                let text = serde_json::to_string(value).map_err(serde::ser::Error::custom)?;
+                serializer.serialize_newtype_variant("SessionValue", 0, "json", &text)
+            }
+            SessionValue::Binary(value) if human_readable => {
+                let text = BASE64.encode(value);
+                serializer.serialize_newtype_variant("SessionValue", 1, "binary", &text)
+            }
+            SessionValue::Binary(value) => {
+                serializer.serialize_newtype_variant("SessionValue", 1, "binary", &Bytes(value))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let human_readable = deserializer.is_human_readable();
+        deserializer.deserialize_enum(
+            "SessionValue",
+            VARIANTS,
+            SessionValueVisitor { human_readable },
+        )
+    }
+}
+
+struct SessionValueVisitor {
+    human_readable: bool,
+}
+
+impl<'de> Visitor<'de> for SessionValueVisitor {
+    type Value = SessionValue;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a tagged session value")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let value = match data.variant()? {
+            (Kind::Json, value) if self.human_readable => {
+                SessionValue::Json(value.newtype_variant()?)
+            }
+            (Kind::Json, value) => {
+                let text = value.newtype_variant::<String>()?;
+                SessionValue::Json(serde_json::from_str(&text).map_err(A::Error::custom)?)
+            }
+            (Kind::Binary, value) if self.human_readable => {
+                let text = value.newtype_variant::<String>()?;
+                SessionValue::Binary(BASE64.decode(text).map_err(A::Error::custom)?)
+            }
+            (Kind::Binary, value) => SessionValue::Binary(value.newtype_variant::<ByteBuf>()?.0),
+        };
+        Ok(value)
+    }
+}
+
+/// Serializes a byte slice as bytes rather than as a sequence of integers.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Owned bytes read from native bytes or, in formats without them, a sequence.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.deserialize_byte_buf(LegacyValueVisitor)? {
+            SessionValue::Binary(value) => Ok(ByteBuf(value)),
+            SessionValue::Json(_) => Err(D::Error::custom("expected a byte sequence")),
+        }
+    }
+}
+
+/// A value in the layout of earlier releases, where JSON values were stored
+/// as encoded strings. Only read from self-describing formats.
+struct LegacyValue(SessionValue);
+
+impl<'de> Deserialize<'de> for LegacyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(LegacyValueVisitor)
+            .map(LegacyValue)
+    }
+}
+
+struct LegacyValueVisitor;
+
+impl<'de> Visitor<'de> for LegacyValueVisitor {
+    type Value = SessionValue;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a JSON string or a byte sequence")
+    }
+
+    fn visit_str<E: Error>(self, value: &str) -> Result<Self::Value, E> {
+        serde_json::from_str(value)
+            .map(SessionValue::Json)
+            .map_err(E::custom)
+    }
+
+    fn visit_bytes<E: Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        Ok(SessionValue::Binary(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: Error>(self, value: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(SessionValue::Binary(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut value = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(byte) = seq.next_element()? {
+            value.push(byte);
+        }
+        Ok(SessionValue::Binary(value))
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionState {
+    version: u32,
+    metadata: SessionMetadata,
+    values: HashMap<String, SessionValue>,
+}
+
+impl Serialize for SessionState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SessionState", 3)?;
+        state.serialize_field("version", &self.version)?;
+        state.serialize_field("metadata", &self.metadata)?;
+        state.serialize_field("entries", &self.values)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = if deserializer.is_human_readable() {
+            SessionStateRepr::deserialize(deserializer)?
+        } else {
+            SessionStateRepr::Current(Current::deserialize(deserializer)?)
+        };
+        Ok(repr.into())
+    }
+}
+
+/// The layout written by this release, and the only one read from formats
+/// that are not human-readable, since those cannot be probed for older
+/// layouts.
+#[derive(Deserialize)]
+#[serde(rename = "SessionState")]
+struct Current {
+    version: u32,
+    #[serde(default = "SessionMetadata::legacy")]
+    metadata: SessionMetadata,
+    entries: HashMap<String, SessionValue>,
+}
+
+/// Accepts the current layout, the layout that kept JSON and binary values
+/// in separate maps, the layout of earlier releases that stored JSON values
+/// as encoded strings, and the unversioned map stored before that, which is
+/// read as version 0. Missing metadata is recreated.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SessionStateRepr {
+    Current(Current),
+    Split {
+        version: u32,
+        #[serde(default = "SessionMetadata::legacy")]
+        metadata: SessionMetadata,
+        data: HashMap<String, serde_json::Value>,
+        #[serde(default)]
+        binary: HashMap<String, ByteBuf>,
+    },
+    Versioned {
+        version: u32,
+        #[serde(default = "SessionMetadata::legacy")]
+        metadata: SessionMetadata,
+        values: HashMap<String, LegacyValue>,
+    },
+    Unversioned(HashMap<String, LegacyValue>),
+}
+
+impl From<SessionStateRepr> for SessionState {
+    fn from(repr: SessionStateRepr) -> Self {
+        let legacy = |values: HashMap<String, LegacyValue>| {
+            values
+                .into_iter()
+                .map(|(key, LegacyValue(value))| (key, value))
+                .collect()
+        };
+        match repr {
+            SessionStateRepr::Current(Current {
+                version,
+                metadata,
+                entries,
+            }) => Self {
+                version,
+                metadata,
+                values: entries,
+            },
+            SessionStateRepr::Split {
+                version,
+                metadata,
+                data,
+                binary,
+            } => {
+                let json = data
+                    .into_iter()
+                    .map(|(key, value)| (key, SessionValue::Json(value)));
+                let binary = binary
+                    .into_iter()
+                    .map(|(key, ByteBuf(value))| (key, SessionValue::Binary(value)));
+                Self {
+                    version,
+                    metadata,
+                    values: json.chain(binary).collect(),
+                }
+            }
+            SessionStateRepr::Versioned {
+                version,
+                metadata,
+                values,
+            } => Self {
+                version,
+                metadata,
+                values: legacy(values),
+            },
+            SessionStateRepr::Unversioned(values) => Self {
+                values: legacy(values),
+                metadata: SessionMetadata::legacy(),
+                ..Default::default()
+            },
+        }
+    }
+}
 
 impl SessionState {
-    pub fn insert(&mut self, key: &str, value: String) -> Option<String> {
-        self.0.insert(key.to_string(), value)
+    /// The schema version of the stored values, see [`crate::SessionMigrator`].
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn set_version(&mut self, version: u32) {
+        self.version = version;
+    }
+
+    pub fn metadata(&self) -> &SessionMetadata {
+        &self.metadata
+    }
+
+    pub(crate) fn metadata_mut(&mut self) -> &mut SessionMetadata {
+        &mut self.metadata
+    }
+
+    pub fn insert(&mut self, key: &str, value: impl Into<SessionValue>) -> Option<SessionValue> {
+        self.values.insert(key.to_string(), value.into())
     }
 
-    pub fn remove(&mut self, key: &str) -> Option<String> {
-        self.0.remove(key)
+    pub fn remove(&mut self, key: &str) -> Option<SessionValue> {
+        self.values.remove(key)
     }
 
-    pub fn get(&self, key: &str) -> Option<&String> {
-        self.0.get(key)
+    pub fn get(&self, key: &str) -> Option<&SessionValue> {
+        self.values.get(key)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SessionValue)> {
+        self.values.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn serialize_stores_json_values_natively() {
+        let mut state = SessionState::default();
+        state.insert("user", json!({ "id": 1 }));
+        state.insert("blob", vec![0u8, 255]);
+        let json = serde_json::to_value(&state).expect("expected state to serialize");
+        assert_eq!(json["entries"]["user"], json!({ "json": { "id": 1 } }));
+        assert_eq!(json["entries"]["blob"], json!({ "binary": "AP8=" }));
+    }
+
+    #[test]
+    fn deserialize_reads_the_split_layout() {
+        let json = r#"{"version":1,"data":{"id":"abc"},"binary":{"blob":[1,2,3]}}"#;
+        let state =
+            serde_json::from_str::<SessionState>(json).expect("expected state to deserialize");
+        assert_eq!(state.get("id"), Some(&SessionValue::Json(json!("abc"))));
+        assert_eq!(
+            state.get("blob"),
+            Some(&SessionValue::Binary(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn deserialize_reads_the_encoded_layout_of_earlier_releases() {
+        let json = r#"{"version":2,"values":{"id":"\"abc\"","blob":[1,2,3]}}"#;
+        let state =
+            serde_json::from_str::<SessionState>(json).expect("expected state to deserialize");
+        assert_eq!(state.version(), 2);
+        assert_eq!(state.get("id"), Some(&SessionValue::Json(json!("abc"))));
+        assert_eq!(
+            state.get("blob"),
+            Some(&SessionValue::Binary(vec![1, 2, 3]))
+        );
+    }
+
+    #[test]
+    fn deserialize_reads_unversioned_state_as_version_zero() {
+        let state = serde_json::from_str::<SessionState>(r#"{"id":"\"abc\""}"#)
+            .expect("expected state to deserialize");
+        assert_eq!(state.version(), 0);
+        assert_eq!(state.get("id"), Some(&SessionValue::Json(json!("abc"))));
+    }
+
+    #[test]
+    fn serialize_round_trips_json_and_binary_values() {
+        let mut state = SessionState::default();
+        state.insert("list", json!([1, 2, 3]));
+        state.insert("blob", vec![0u8, 255]);
+        let json = serde_json::to_string(&state).expect("expected state to serialize");
+        let restored =
+            serde_json::from_str::<SessionState>(&json).expect("expected state to deserialize");
+        assert_eq!(restored, state);
+    }
+
+    #[test]
+    fn serialize_round_trips_through_binary_formats() {
+        let mut state = SessionState::default();
+        state.insert("user", json!({ "id": 1, "roles": ["admin"] }));
+        state.insert("blob", vec![0u8, 255]);
+        let bytes = bincode::serialize(&state).expect("expected state to serialize");
+        let restored =
+            bincode::deserialize::<SessionState>(&bytes).expect("expected state to deserialize");
+        assert_eq!(restored, state);
+
+        let bytes = postcard::to_allocvec(&state).expect("expected state to serialize");
+        let restored =
+            postcard::from_bytes::<SessionState>(&bytes).expect("expected state to deserialize");
+        assert_eq!(restored, state);
     }
 }
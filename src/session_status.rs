@@ -0,0 +1,10 @@
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum SessionStatus {
+    /// The session has been modified since it was loaded or last saved.
+    Changed,
+    /// The session matches what is in storage, or holds nothing worth saving.
+    #[default]
+    Unchanged,
+    /// The session has been removed from storage and can no longer be modified.
+    Destroyed,
+}
@@ -2,16 +2,43 @@ use std::time::Duration;
 
 use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite, Table};
 
-use crate::{session::Session, session_state::SessionState, SessionKey};
+use crate::{
+    instrument::{record_payload, Operation},
+    session::{Session, SessionError},
+    session_state::SessionState,
+    SessionKey,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum SessionStorageError<StorageError> {
     #[error("Serialization error")]
     SerializationError,
+    #[error("Session \"{0}\" already exists")]
+    ConflictError(SessionKey),
+    #[error("Session \"{0}\" does not exist")]
+    NotFoundError(SessionKey),
+    #[error("Session \"{0}\" was logged out")]
+    LoggedOutError(SessionKey),
+    #[error("Session \"{0}\" was destroyed")]
+    DestroyedError(SessionKey),
+    #[error("Session \"{0}\" was modified concurrently")]
+    RevisionConflictError(SessionKey),
+    #[error("Session payload of {0} bytes exceeds the limit of {1} bytes")]
+    PayloadTooLargeError(usize, usize),
     #[error(transparent)]
     StorageError(#[from] StorageError),
 }
 
+impl<StorageError> SessionStorageError<StorageError> {
+    /// Converts an error from regenerating the key of session `session_key`.
+    pub(crate) fn from_regenerate(session_key: &SessionKey, error: SessionError) -> Self {
+        match error {
+            SessionError::SessionDestroyedError => Self::DestroyedError(session_key.clone()),
+            _ => Self::SerializationError,
+        }
+    }
+}
+
 pub struct SessionStateTable {}
 
 impl Table for SessionStateTable {
@@ -44,6 +71,12 @@ where
     Self: Storage,
 {
     fn session_save(&mut self, session: &Session) -> Result<(), SessionStorageError<Self::Error>>;
+    /// Removes the session and returns it in one storage call, so a
+    /// single-use session can be read only once.
+    fn session_take(
+        &mut self,
+        session_key: &SessionKey,
+    ) -> Result<Option<Session>, SessionStorageError<Self::Error>>;
     fn session_destroy(
         &mut self,
         session_key: &SessionKey,
@@ -58,7 +91,8 @@ where
         &self,
         session_key: &SessionKey,
     ) -> Result<bool, SessionStorageError<Self::Error>> {
-        let exists = self.exists(session_key)?;
+        let operation = Operation::start("session_exists", session_key);
+        let exists = operation.finish(self.exists(session_key))?;
         Ok(exists)
     }
 
@@ -66,7 +100,8 @@ where
         &self,
         session_key: &SessionKey,
     ) -> Result<Option<Session>, SessionStorageError<Self::Error>> {
-        let state = self.get(session_key)?;
+        let operation = Operation::start("session_load", session_key);
+        let state = operation.finish_lookup(self.get(session_key))?;
         let session = state.map(|state| Session::new(session_key.clone(), state.into_owned()));
         Ok(session)
     }
@@ -75,7 +110,8 @@ where
         &self,
         session_key: &SessionKey,
     ) -> Result<Duration, SessionStorageError<Self::Error>> {
-        let ttl = self.ttl(session_key)?;
+        let operation = Operation::start("session_ttl", session_key);
+        let ttl = operation.finish(self.ttl(session_key))?;
         Ok(ttl)
     }
 }
@@ -86,16 +122,29 @@ where
 {
     fn session_save(&mut self, session: &Session) -> Result<(), SessionStorageError<Self::Error>> {
         let session_id = session.id();
-        let state: SessionState = session.into();
-        self.insert(session_id, &state)?;
+        let state = session.state();
+        record_payload(state);
+        let operation = Operation::start("session_save", session_id);
+        operation.finish(self.insert(session_id, state))?;
         Ok(())
     }
 
+    fn session_take(
+        &mut self,
+        session_key: &SessionKey,
+    ) -> Result<Option<Session>, SessionStorageError<Self::Error>> {
+        let operation = Operation::start("session_take", session_key);
+        let state = operation.finish_lookup(self.remove(session_key))?;
+        let session = state.map(|state| Session::new(session_key.clone(), state));
+        Ok(session)
+    }
+
     fn session_destroy(
         &mut self,
         session_key: &SessionKey,
     ) -> Result<(), SessionStorageError<Self::Error>> {
-        self.remove(session_key)?;
+        let operation = Operation::start("session_destroy", session_key);
+        operation.finish(self.remove(session_key))?;
         Ok(())
     }
 }
@@ -157,7 +206,7 @@ mod test {
         let mut storage = TestStorage::new();
         let key = SessionKey::generate();
         let mut state = SessionState::default();
-        state.insert("id", "abc".to_string());
+        state.insert("id", serde_json::json!("abc"));
         storage
             .insert(&key, &state)
             .expect("Failed to insert session state");
@@ -168,7 +217,7 @@ mod test {
         let mut storage = TestStorage::new();
         let key = SessionKey::generate();
         let mut state = SessionState::default();
-        state.insert("id", "abc".to_string());
+        state.insert("id", serde_json::json!("abc"));
         storage
             .insert(&key, &state)
             .expect("Failed to insert session state");
@@ -185,7 +234,7 @@ mod test {
         let mut storage = TestStorage::new();
         let key = SessionKey::generate();
         let mut state = SessionState::default();
-        state.insert("id", "abc".to_string());
+        state.insert("id", serde_json::json!("abc"));
         storage
             .insert(&key, &state)
             .expect("Failed to insert session state");
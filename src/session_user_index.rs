@@ -0,0 +1,496 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite, Table};
+
+use crate::{
+    instrument, session_storage::SessionStateTable, SessionKey, SessionState, SessionStorageError,
+};
+
+/// Maps a user id to the keys of that user's sessions, oldest first.
+pub struct UserSessionsTable {}
+
+impl Table for UserSessionsTable {
+    type Key = String;
+    type OwnedKey = Self::Key;
+    type Value = Vec<SessionKey>;
+    type OwnedValue = Self::Value;
+}
+
+pub trait SessionUserIndexRead
+where
+    Self: Storage,
+{
+    /// Returns the keys of the sessions indexed for `user_id`. Sessions that
+    /// expired without being destroyed remain listed.
+    fn sessions_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<SessionKey>, SessionStorageError<Self::Error>>;
+}
+
+impl<S> SessionUserIndexRead for S
+where
+    S: StorageRead<UserSessionsTable>,
+{
+    fn sessions_for_user(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<SessionKey>, SessionStorageError<Self::Error>> {
+        let sessions = self.get(&user_id.to_string())?;
+        Ok(sessions.map(Cow::into_owned).unwrap_or_default())
+    }
+}
+
+pub trait SessionUserIndexWrite
+where
+    Self: Storage,
+{
+    /// Destroys every session indexed for `user_id` and clears the index entry,
+    /// returning the destroyed keys. Used to log a user out of every device.
+    ///
+    /// This is not atomic: sessions are destroyed one at a time. If one fails,
+    /// those destroyed before it stay destroyed, the index entry is cut down
+    /// to the sessions still alive, and the error is returned, so calling it
+    /// again finishes the job.
+    fn destroy_for_user(
+        &mut self,
+        user_id: &str,
+    ) -> Result<Vec<SessionKey>, SessionStorageError<Self::Error>>;
+}
+
+impl<S> SessionUserIndexWrite for S
+where
+    S: StorageRead<UserSessionsTable>
+        + StorageWrite<UserSessionsTable>
+        + StorageWrite<SessionStateTable>,
+{
+    fn destroy_for_user(
+        &mut self,
+        user_id: &str,
+    ) -> Result<Vec<SessionKey>, SessionStorageError<Self::Error>> {
+        let sessions = self.sessions_for_user(user_id)?;
+        let user_id = user_id.to_string();
+        for (destroyed, session) in sessions.iter().enumerate() {
+            if let Err(e) = StorageWrite::<SessionStateTable>::remove(self, session) {
+                let remaining = sessions[destroyed..].to_vec();
+                // Should pruning fail too, the index still lists keys that are
+                // already gone, as it does for expired sessions.
+                if destroyed > 0
+                    && StorageWrite::<UserSessionsTable>::insert(self, &user_id, &remaining)
+                        .is_err()
+                {
+                    instrument::warn(session, "failed to prune the user index");
+                }
+                return Err(e.into());
+            }
+        }
+        StorageWrite::<UserSessionsTable>::remove(self, &user_id)?;
+        Ok(sessions)
+    }
+}
+
+/// Wraps session storage and keeps [`UserSessionsTable`] in sync with the
+/// user id of each session as sessions are saved and destroyed.
+pub struct UserIndex<S> {
+    storage: S,
+    max_sessions: Option<usize>,
+}
+
+impl<S> UserIndex<S> {
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            max_sessions: None,
+        }
+    }
+
+    pub fn max_sessions(&self) -> Option<usize> {
+        self.max_sessions
+    }
+
+    /// Limits how many sessions a user may have at once. Saving a session that
+    /// exceeds the limit destroys the user's oldest sessions.
+    pub fn set_max_sessions(&mut self, limit: usize) {
+        self.max_sessions = Some(limit);
+    }
+
+    pub fn into_inner(self) -> S {
+        self.storage
+    }
+}
+
+impl<S> UserIndex<S>
+where
+    S: StorageRead<UserSessionsTable> + StorageWrite<UserSessionsTable>,
+{
+    /// Adds `key` to the user's sessions, returning the oldest sessions that
+    /// no longer fit within the limit. They are removed from the index, but
+    /// the caller must destroy them.
+    fn index(&mut self, user_id: &str, key: &SessionKey) -> Result<Vec<SessionKey>, S::Error> {
+        let user_id = user_id.to_string();
+        let mut sessions = self
+            .storage
+            .get(&user_id)?
+            .map(Cow::into_owned)
+            .unwrap_or_default();
+        if sessions.contains(key) {
+            return Ok(Vec::new());
+        }
+        sessions.push(key.clone());
+        let excess = self
+            .max_sessions
+            .map_or(0, |limit| sessions.len().saturating_sub(limit));
+        let evicted = sessions.drain(..excess).collect();
+        StorageWrite::<UserSessionsTable>::insert(&mut self.storage, &user_id, &sessions)?;
+        Ok(evicted)
+    }
+
+    fn unindex(&mut self, user_id: &str, key: &SessionKey) -> Result<(), S::Error> {
+        let user_id = user_id.to_string();
+        let Some(sessions) = self.storage.get(&user_id)? else {
+            return Ok(());
+        };
+        let sessions = sessions
+            .iter()
+            .filter(|session| *session != key)
+            .cloned()
+            .collect::<Vec<_>>();
+        if sessions.is_empty() {
+            StorageWrite::<UserSessionsTable>::remove(&mut self.storage, &user_id)?;
+        } else {
+            StorageWrite::<UserSessionsTable>::insert(&mut self.storage, &user_id, &sessions)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Storage> Storage for UserIndex<S> {
+    type Error = S::Error;
+}
+
+impl<S> StorageRead<SessionStateTable> for UserIndex<S>
+where
+    S: StorageRead<SessionStateTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.storage.get(key)
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.storage.exists(key)
+    }
+}
+
+impl<S> StorageTemp<SessionStateTable> for UserIndex<S>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.storage.ttl(key)
+    }
+}
+
+impl<S> StorageRead<UserSessionsTable> for UserIndex<S>
+where
+    S: StorageRead<UserSessionsTable>,
+{
+    fn get(&self, key: &String) -> Result<Option<Cow<'_, Vec<SessionKey>>>, Self::Error> {
+        self.storage.get(key)
+    }
+
+    fn exists(&self, key: &String) -> Result<bool, Self::Error> {
+        self.storage.exists(key)
+    }
+}
+
+impl<S> StorageWrite<UserSessionsTable> for UserIndex<S>
+where
+    S: StorageWrite<UserSessionsTable>,
+{
+    fn insert(
+        &mut self,
+        key: &String,
+        value: &Vec<SessionKey>,
+    ) -> Result<Option<Vec<SessionKey>>, Self::Error> {
+        self.storage.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &String) -> Result<Option<Vec<SessionKey>>, Self::Error> {
+        self.storage.remove(key)
+    }
+}
+
+impl<S> StorageWrite<SessionStateTable> for UserIndex<S>
+where
+    S: StorageWrite<SessionStateTable>
+        + StorageRead<UserSessionsTable>
+        + StorageWrite<UserSessionsTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        let previous = StorageWrite::<SessionStateTable>::insert(&mut self.storage, key, value)?;
+        let previous_user = previous
+            .as_ref()
+            .and_then(|state| state.metadata().user_id());
+        let user = value.metadata().user_id();
+        if previous_user != user {
+            if let Some(previous_user) = previous_user {
+                self.unindex(previous_user, key)?;
+            }
+            if let Some(user) = user {
+                // A rotated session takes the place of its previous key
+                // rather than counting against the limit a second time.
+                if let Some(replaced) = value.metadata().replaces() {
+                    self.unindex(user, replaced)?;
+                }
+                for evicted in self.index(user, key)? {
+                    StorageWrite::<SessionStateTable>::remove(&mut self.storage, &evicted)?;
+                }
+            }
+        }
+        Ok(previous)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        let previous = StorageWrite::<SessionStateTable>::remove(&mut self.storage, key)?;
+        if let Some(user) = previous
+            .as_ref()
+            .and_then(|state| state.metadata().user_id())
+        {
+            self.unindex(user, key)?;
+        }
+        Ok(previous)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{borrow::Cow, collections::HashMap, time::Duration};
+
+    use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+    use super::{SessionUserIndexRead, SessionUserIndexWrite, UserIndex, UserSessionsTable};
+    use crate::{session_storage::SessionStateTable, SessionKey, SessionModel, SessionState};
+
+    struct TestStorage {
+        sessions: HashMap<SessionKey, SessionState>,
+        users: HashMap<String, Vec<SessionKey>>,
+        fail_remove: Option<SessionKey>,
+    }
+
+    impl TestStorage {
+        fn new() -> Self {
+            TestStorage {
+                sessions: HashMap::new(),
+                users: HashMap::new(),
+                fail_remove: None,
+            }
+        }
+    }
+
+    impl Storage for TestStorage {
+        type Error = String;
+    }
+
+    impl StorageRead<SessionStateTable> for TestStorage {
+        fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+            Ok(self.sessions.get(key).map(Cow::Borrowed))
+        }
+
+        fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+            Ok(self.sessions.contains_key(key))
+        }
+    }
+
+    impl StorageWrite<SessionStateTable> for TestStorage {
+        fn insert(
+            &mut self,
+            key: &SessionKey,
+            value: &SessionState,
+        ) -> Result<Option<SessionState>, Self::Error> {
+            Ok(self.sessions.insert(key.clone(), value.clone()))
+        }
+
+        fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+            if self.fail_remove.as_ref() == Some(key) {
+                return Err("connection reset".to_string());
+            }
+            Ok(self.sessions.remove(key))
+        }
+    }
+
+    impl StorageTemp<SessionStateTable> for TestStorage {
+        fn ttl(&self, _key: &SessionKey) -> Result<Duration, Self::Error> {
+            Ok(Duration::from_secs(100))
+        }
+    }
+
+    impl StorageRead<UserSessionsTable> for TestStorage {
+        fn get(&self, key: &String) -> Result<Option<Cow<'_, Vec<SessionKey>>>, Self::Error> {
+            Ok(self.users.get(key).map(Cow::Borrowed))
+        }
+
+        fn exists(&self, key: &String) -> Result<bool, Self::Error> {
+            Ok(self.users.contains_key(key))
+        }
+    }
+
+    impl StorageWrite<UserSessionsTable> for TestStorage {
+        fn insert(
+            &mut self,
+            key: &String,
+            value: &Vec<SessionKey>,
+        ) -> Result<Option<Vec<SessionKey>>, Self::Error> {
+            Ok(self.users.insert(key.clone(), value.clone()))
+        }
+
+        fn remove(&mut self, key: &String) -> Result<Option<Vec<SessionKey>>, Self::Error> {
+            Ok(self.users.remove(key))
+        }
+    }
+
+    #[test]
+    fn save_indexes_the_session_by_user() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(UserIndex::new(&mut storage), Duration::from_secs(100));
+        model
+            .set_user_id(Some("brandon".to_string()))
+            .expect("Failed to set user id");
+        model.save().expect("Failed to save session model");
+        let id = model.id().clone();
+
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert_eq!(sessions, vec![id]);
+    }
+
+    #[test]
+    fn destroy_removes_the_session_from_the_index() {
+        let mut storage = TestStorage::new();
+        let mut model = SessionModel::new(UserIndex::new(&mut storage), Duration::from_secs(100));
+        model
+            .set_user_id(Some("brandon".to_string()))
+            .expect("Failed to set user id");
+        model.save().expect("Failed to save session model");
+        model.destroy().expect("Failed to destroy session model");
+
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn destroy_for_user_destroys_every_session_of_the_user() {
+        let mut storage = TestStorage::new();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut model =
+                SessionModel::new(UserIndex::new(&mut storage), Duration::from_secs(100));
+            model
+                .set_user_id(Some("brandon".to_string()))
+                .expect("Failed to set user id");
+            model.save().expect("Failed to save session model");
+            ids.push(model.id().clone());
+        }
+
+        let destroyed = storage
+            .destroy_for_user("brandon")
+            .expect("Failed to destroy sessions for user");
+        assert_eq!(destroyed, ids);
+        for id in &ids {
+            let state = StorageRead::<SessionStateTable>::get(&storage, id)
+                .expect("Failed to get session state");
+            assert!(state.is_none());
+        }
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn destroy_for_user_prunes_the_index_when_a_destroy_fails() {
+        let mut storage = TestStorage::new();
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let mut model =
+                SessionModel::new(UserIndex::new(&mut storage), Duration::from_secs(100));
+            model
+                .set_user_id(Some("brandon".to_string()))
+                .expect("Failed to set user id");
+            model.save().expect("Failed to save session model");
+            ids.push(model.id().clone());
+        }
+        storage.fail_remove = Some(ids[1].clone());
+
+        let result = storage.destroy_for_user("brandon");
+        assert!(result.is_err());
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert_eq!(sessions, ids[1..]);
+    }
+
+    #[test]
+    fn save_evicts_the_oldest_session_over_the_limit() {
+        let mut storage = TestStorage::new();
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let mut index = UserIndex::new(&mut storage);
+            index.set_max_sessions(2);
+            let mut model = SessionModel::new(index, Duration::from_secs(100));
+            model
+                .set_user_id(Some("brandon".to_string()))
+                .expect("Failed to set user id");
+            model.save().expect("Failed to save session model");
+            ids.push(model.id().clone());
+        }
+
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert_eq!(sessions, ids[1..]);
+        let oldest = StorageRead::<SessionStateTable>::get(&storage, &ids[0])
+            .expect("Failed to get session state");
+        assert!(oldest.is_none());
+    }
+
+    #[test]
+    fn rotating_a_session_does_not_evict_the_others() {
+        let mut storage = TestStorage::new();
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let mut index = UserIndex::new(&mut storage);
+            index.set_max_sessions(2);
+            let mut model = SessionModel::new(index, Duration::from_secs(100));
+            let id = model
+                .authenticate("brandon".to_string())
+                .expect("Failed to authenticate session");
+            ids.push(id);
+        }
+
+        let mut index = UserIndex::new(&mut storage);
+        index.set_max_sessions(2);
+        let mut model = SessionModel::load(index, &ids[1])
+            .expect("Failed to load session model")
+            .expect("Expected session model to be present");
+        model.regenerate().expect("Failed to regenerate session");
+        let id = model
+            .elevate(Duration::from_secs(60))
+            .expect("Failed to elevate session");
+        drop(model);
+
+        let sessions = storage
+            .sessions_for_user("brandon")
+            .expect("Failed to read user index");
+        assert_eq!(sessions, vec![ids[0].clone(), id]);
+        let first = StorageRead::<SessionStateTable>::get(&storage, &ids[0])
+            .expect("Failed to get session state");
+        assert!(first.is_some());
+    }
+}
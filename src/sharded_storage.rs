@@ -0,0 +1,142 @@
+use std::{borrow::Cow, time::Duration};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{session_storage::SessionStateTable, SessionKey, SessionState};
+
+/// Spreads sessions across several storages by hashing their keys, so one
+/// backend instance does not have to hold every session.
+///
+/// Keys are placed with rendezvous hashing: adding or removing a shard only
+/// moves the sessions that land on or left that shard. The hash is stable
+/// across processes and releases, so every instance routes a key the same way.
+pub struct ShardedStorage<S> {
+    shards: Vec<S>,
+}
+
+impl<S> ShardedStorage<S> {
+    /// # Panics
+    ///
+    /// Panics if `shards` is empty.
+    pub fn new(shards: Vec<S>) -> Self {
+        assert!(
+            !shards.is_empty(),
+            "ShardedStorage needs at least one shard"
+        );
+        Self { shards }
+    }
+
+    pub fn into_inner(self) -> Vec<S> {
+        self.shards
+    }
+
+    /// The index of the shard that holds `key`.
+    pub fn shard_for(&self, key: &SessionKey) -> usize {
+        (0..self.shards.len())
+            .max_by_key(|&shard| weight(key, shard))
+            .expect("there is at least one shard")
+    }
+
+    fn shard(&self, key: &SessionKey) -> &S {
+        &self.shards[self.shard_for(key)]
+    }
+
+    fn shard_mut(&mut self, key: &SessionKey) -> &mut S {
+        let shard = self.shard_for(key);
+        &mut self.shards[shard]
+    }
+}
+
+/// 64-bit FNV-1a of the key followed by the shard index.
+fn weight(key: &SessionKey, shard: usize) -> u64 {
+    let shard = (shard as u64).to_le_bytes();
+    let bytes = key.as_ref().as_bytes().iter().chain(&shard);
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl<S: Storage> Storage for ShardedStorage<S> {
+    type Error = S::Error;
+}
+
+impl<S> StorageRead<SessionStateTable> for ShardedStorage<S>
+where
+    S: StorageRead<SessionStateTable>,
+{
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.shard(key).get(key)
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.shard(key).exists(key)
+    }
+}
+
+impl<S> StorageWrite<SessionStateTable> for ShardedStorage<S>
+where
+    S: StorageWrite<SessionStateTable>,
+{
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        self.shard_mut(key).insert(key, value)
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.shard_mut(key).remove(key)
+    }
+}
+
+impl<S> StorageTemp<SessionStateTable> for ShardedStorage<S>
+where
+    S: StorageTemp<SessionStateTable>,
+{
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.shard(key).ttl(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_util::MockStorage, Session, SessionStorageRead, SessionStorageWrite};
+
+    #[test]
+    fn sharded_storage_stores_each_session_on_one_shard() {
+        let shards = (0..3).map(|_| MockStorage::new()).collect();
+        let mut storage = ShardedStorage::new(shards);
+        let sessions = (0..30).map(|_| Session::default()).collect::<Vec<_>>();
+        for session in &sessions {
+            storage
+                .session_save(session)
+                .expect("expected save to succeed");
+        }
+        for session in &sessions {
+            let loaded = storage
+                .session_load(session.id())
+                .expect("expected load to succeed");
+            assert!(loaded.is_some());
+        }
+
+        let shards = storage.into_inner();
+        assert_eq!(
+            shards.iter().map(|shard| shard.map.len()).sum::<usize>(),
+            30
+        );
+        assert!(shards.iter().all(|shard| !shard.map.is_empty()));
+    }
+
+    #[test]
+    fn adding_a_shard_only_moves_keys_onto_it() {
+        let two = ShardedStorage::new(vec![(), ()]);
+        let three = ShardedStorage::new(vec![(), (), ()]);
+        for _ in 0..100 {
+            let key = SessionKey::generate();
+            let shard = three.shard_for(&key);
+            assert!(shard == 2 || shard == two.shard_for(&key));
+        }
+    }
+}
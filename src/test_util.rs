@@ -0,0 +1,404 @@
+//! Test helpers for code that depends on session storage, and a conformance
+//! suite for custom storage implementations.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    sync::Mutex,
+    time::Duration,
+};
+
+use lushus_storage::{Storage, StorageRead, StorageTemp, StorageWrite};
+
+use crate::{
+    Session, SessionKey, SessionState, SessionStateTable, SessionStorageRead, SessionStorageWrite,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum MockOperation {
+    Get,
+    Exists,
+    Insert,
+    Remove,
+    Ttl,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MockCall {
+    pub operation: MockOperation,
+    pub key: SessionKey,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+#[error("{0}")]
+pub struct MockStorageError(pub String);
+
+/// In-memory session storage that records every call and can be scripted to
+/// fail or stall on specific operations.
+#[derive(Default)]
+pub struct MockStorage {
+    pub(crate) map: HashMap<SessionKey, SessionState>,
+    #[cfg(test)]
+    pub(crate) epoch: Option<u64>,
+    #[cfg(test)]
+    pub(crate) refresh_tokens: HashMap<String, crate::RefreshToken>,
+    ttl: Duration,
+    calls: Mutex<Vec<MockCall>>,
+    failures: Mutex<HashMap<MockOperation, VecDeque<MockStorageError>>>,
+    latencies: HashMap<MockOperation, Duration>,
+}
+
+impl MockStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the TTL reported for every session.
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Makes the next call to `operation` fail with `error`. Failures queue up
+    /// and are consumed in order.
+    pub fn fail_next(&self, operation: MockOperation, error: MockStorageError) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.entry(operation).or_default().push_back(error);
+    }
+
+    /// Delays every call to `operation` by `latency`.
+    pub fn set_latency(&mut self, operation: MockOperation, latency: Duration) {
+        self.latencies.insert(operation, latency);
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    pub fn call_count(&self, operation: MockOperation) -> usize {
+        let calls = self.calls.lock().unwrap();
+        calls.iter().filter(|c| c.operation == operation).count()
+    }
+
+    /// Panics unless `operation` was called with `key`.
+    pub fn assert_called(&self, operation: MockOperation, key: &SessionKey) {
+        let call = MockCall {
+            operation,
+            key: key.clone(),
+        };
+        let calls = self.calls();
+        assert!(
+            calls.contains(&call),
+            "expected {call:?} in recorded calls {calls:?}"
+        );
+    }
+
+    /// Panics if `operation` was called at all.
+    pub fn assert_not_called(&self, operation: MockOperation) {
+        let count = self.call_count(operation);
+        assert_eq!(count, 0, "expected no {operation:?} calls, found {count}");
+    }
+
+    fn record(&self, operation: MockOperation, key: &SessionKey) -> Result<(), MockStorageError> {
+        let call = MockCall {
+            operation,
+            key: key.clone(),
+        };
+        self.calls.lock().unwrap().push(call);
+        if let Some(latency) = self.latencies.get(&operation) {
+            std::thread::sleep(*latency);
+        }
+        let mut failures = self.failures.lock().unwrap();
+        match failures.get_mut(&operation).and_then(VecDeque::pop_front) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Storage for MockStorage {
+    type Error = MockStorageError;
+}
+
+impl StorageRead<SessionStateTable> for MockStorage {
+    fn get(&self, key: &SessionKey) -> Result<Option<Cow<'_, SessionState>>, Self::Error> {
+        self.record(MockOperation::Get, key)?;
+        Ok(self.map.get(key).map(Cow::Borrowed))
+    }
+
+    fn exists(&self, key: &SessionKey) -> Result<bool, Self::Error> {
+        self.record(MockOperation::Exists, key)?;
+        Ok(self.map.contains_key(key))
+    }
+}
+
+impl StorageWrite<SessionStateTable> for MockStorage {
+    fn insert(
+        &mut self,
+        key: &SessionKey,
+        value: &SessionState,
+    ) -> Result<Option<SessionState>, Self::Error> {
+        self.record(MockOperation::Insert, key)?;
+        Ok(self.map.insert(key.clone(), value.clone()))
+    }
+
+    fn remove(&mut self, key: &SessionKey) -> Result<Option<SessionState>, Self::Error> {
+        self.record(MockOperation::Remove, key)?;
+        Ok(self.map.remove(key))
+    }
+}
+
+impl StorageTemp<SessionStateTable> for MockStorage {
+    fn ttl(&self, key: &SessionKey) -> Result<Duration, Self::Error> {
+        self.record(MockOperation::Ttl, key)?;
+        Ok(self.ttl)
+    }
+}
+
+// The crate's own unit tests also need epoch and refresh-token tables; they are
+// left out of the test-util surface.
+#[cfg(test)]
+impl StorageRead<crate::EpochTable> for MockStorage {
+    fn get(&self, _key: &()) -> Result<Option<Cow<'_, u64>>, Self::Error> {
+        Ok(self.epoch.as_ref().map(Cow::Borrowed))
+    }
+
+    fn exists(&self, _key: &()) -> Result<bool, Self::Error> {
+        Ok(self.epoch.is_some())
+    }
+}
+
+#[cfg(test)]
+impl StorageWrite<crate::EpochTable> for MockStorage {
+    fn insert(&mut self, _key: &(), value: &u64) -> Result<Option<u64>, Self::Error> {
+        Ok(self.epoch.replace(*value))
+    }
+
+    fn remove(&mut self, _key: &()) -> Result<Option<u64>, Self::Error> {
+        Ok(self.epoch.take())
+    }
+}
+
+#[cfg(test)]
+impl StorageRead<crate::RefreshTokenTable> for MockStorage {
+    fn get(&self, key: &String) -> Result<Option<Cow<'_, crate::RefreshToken>>, Self::Error> {
+        Ok(self.refresh_tokens.get(key).map(Cow::Borrowed))
+    }
+
+    fn exists(&self, key: &String) -> Result<bool, Self::Error> {
+        Ok(self.refresh_tokens.contains_key(key))
+    }
+}
+
+#[cfg(test)]
+impl StorageWrite<crate::RefreshTokenTable> for MockStorage {
+    fn insert(
+        &mut self,
+        key: &String,
+        value: &crate::RefreshToken,
+    ) -> Result<Option<crate::RefreshToken>, Self::Error> {
+        Ok(self.refresh_tokens.insert(key.clone(), value.clone()))
+    }
+
+    fn remove(&mut self, key: &String) -> Result<Option<crate::RefreshToken>, Self::Error> {
+        Ok(self.refresh_tokens.remove(key))
+    }
+}
+
+#[cfg(test)]
+impl StorageTemp<crate::RefreshTokenTable> for MockStorage {
+    fn ttl(&self, key: &String) -> Result<Duration, Self::Error> {
+        let token = self.refresh_tokens.get(key);
+        Ok(token.map_or(Duration::ZERO, crate::RefreshToken::ttl))
+    }
+}
+
+/// Generates a `#[test]` for every conformance check, building a fresh storage
+/// from `$storage` for each.
+///
+/// ```ignore
+/// mod conformance {
+///     lushus_session::session_storage_test_suite!(MyStorage::connect_test());
+/// }
+/// ```
+#[macro_export]
+macro_rules! session_storage_test_suite {
+    ($storage:expr) => {
+        #[test]
+        fn session_storage_loads_what_it_saved() {
+            $crate::test_util::check_save_and_load($storage);
+        }
+
+        #[test]
+        fn session_storage_loads_nothing_for_a_missing_key() {
+            $crate::test_util::check_load_missing($storage);
+        }
+
+        #[test]
+        fn session_storage_overwrites_on_save() {
+            $crate::test_util::check_save_overwrites($storage);
+        }
+
+        #[test]
+        fn session_storage_reports_existence() {
+            $crate::test_util::check_exists($storage);
+        }
+
+        #[test]
+        fn session_storage_forgets_destroyed_sessions() {
+            $crate::test_util::check_destroy($storage);
+        }
+
+        #[test]
+        fn session_storage_reports_a_ttl() {
+            $crate::test_util::check_ttl($storage);
+        }
+    };
+}
+
+fn saved_session<S>(storage: &mut S) -> Session
+where
+    S: SessionStorageWrite,
+    S::Error: Debug,
+{
+    let mut session = Session::default();
+    session
+        .insert("id", &"abc".to_string())
+        .expect("Failed to write to session");
+    storage
+        .session_save(&session)
+        .expect("Failed to save session");
+    session
+}
+
+pub fn check_save_and_load<S>(mut storage: S)
+where
+    S: SessionStorageRead + SessionStorageWrite,
+    S::Error: Debug,
+{
+    let session = saved_session(&mut storage);
+    let loaded = storage
+        .session_load(session.id())
+        .expect("Failed to load session")
+        .expect("Expected saved session to be present");
+    assert_eq!(loaded.id(), session.id());
+    assert_eq!(loaded.state(), session.state());
+}
+
+pub fn check_load_missing<S>(storage: S)
+where
+    S: SessionStorageRead,
+    S::Error: Debug,
+{
+    let loaded = storage
+        .session_load(&SessionKey::generate())
+        .expect("Failed to load session");
+    assert!(loaded.is_none(), "expected a missing key to load nothing");
+}
+
+pub fn check_save_overwrites<S>(mut storage: S)
+where
+    S: SessionStorageRead + SessionStorageWrite,
+    S::Error: Debug,
+{
+    let mut session = saved_session(&mut storage);
+    session
+        .insert("id", &"def".to_string())
+        .expect("Failed to write to session");
+    storage
+        .session_save(&session)
+        .expect("Failed to save session");
+    let loaded = storage
+        .session_load(session.id())
+        .expect("Failed to load session")
+        .expect("Expected saved session to be present");
+    let id = loaded.get::<String>("id").expect("Failed to read session");
+    assert_eq!(id, Some("def".to_string()));
+}
+
+pub fn check_exists<S>(mut storage: S)
+where
+    S: SessionStorageRead + SessionStorageWrite,
+    S::Error: Debug,
+{
+    let missing = storage
+        .session_exists(&SessionKey::generate())
+        .expect("Failed to check session existence");
+    assert!(!missing, "expected a missing key not to exist");
+    let session = saved_session(&mut storage);
+    let exists = storage
+        .session_exists(session.id())
+        .expect("Failed to check session existence");
+    assert!(exists, "expected a saved session to exist");
+}
+
+pub fn check_destroy<S>(mut storage: S)
+where
+    S: SessionStorageRead + SessionStorageWrite,
+    S::Error: Debug,
+{
+    let session = saved_session(&mut storage);
+    storage
+        .session_destroy(session.id())
+        .expect("Failed to destroy session");
+    let loaded = storage
+        .session_load(session.id())
+        .expect("Failed to load session");
+    assert!(loaded.is_none(), "expected a destroyed session to be gone");
+    storage
+        .session_destroy(session.id())
+        .expect("Expected destroying a missing session to succeed");
+}
+
+pub fn check_ttl<S>(mut storage: S)
+where
+    S: SessionStorageRead + SessionStorageWrite,
+    S::Error: Debug,
+{
+    let session = saved_session(&mut storage);
+    storage
+        .session_ttl(session.id())
+        .expect("Failed to read session ttl");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SessionModel, SessionStorageError};
+
+    #[test]
+    fn mock_storage_records_calls() {
+        let mut storage = MockStorage::new();
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        model.save().expect("Failed to save session model");
+        let id = model.id().clone();
+
+        storage.assert_called(MockOperation::Insert, &id);
+        storage.assert_not_called(MockOperation::Remove);
+    }
+
+    #[test]
+    fn mock_storage_fails_the_scripted_operation() {
+        let mut storage = MockStorage::new();
+        let error = MockStorageError("connection reset".to_string());
+        storage.fail_next(MockOperation::Insert, error.clone());
+
+        let mut model = SessionModel::new(&mut storage, Duration::from_secs(100));
+        model
+            .insert::<String>("id", "abc".to_string())
+            .expect("Failed to write to session model");
+        let result = model.save();
+        assert!(matches!(result, Err(SessionStorageError::StorageError(e)) if e == error));
+        model.save().expect("Failed to save session model");
+    }
+}
+
+#[cfg(test)]
+mod conformance {
+    use super::MockStorage;
+
+    crate::session_storage_test_suite!(MockStorage::new());
+}